@@ -0,0 +1,236 @@
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use crate::category_tree::is_within;
+use crate::error::{Error, Result};
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::file_io::{read_budgets, read_classified_transactions, InputOptions, SourceTransactions};
+use crate::money::Money;
+use crate::transactions::Transaction;
+
+/// A spending limit for a category, including its subcategories, optionally narrowed to one
+/// description. Limits are positive, and refunds count against spending. Read from
+/// budgets.json, e.g. `[{"category": "Food", "monthly": 600},
+/// {"category": "Shopping", "description": "Amazon", "annual": "1,500"}]`.
+#[derive(Debug, Deserialize)]
+pub struct Budget {
+    pub category: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub monthly: Option<Money>,
+    #[serde(default)]
+    pub annual: Option<Money>,
+}
+
+#[derive(Debug)]
+pub enum BudgetError {
+    Limit { index: usize },
+}
+
+impl fmt::Display for BudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BudgetError::Limit { index } => {
+                write!(f, "budget {} needs exactly one of monthly and annual", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BudgetError {}
+
+impl Budget {
+    fn applies_to(&self, transaction: &Transaction) -> bool {
+        is_within(&transaction.category, &self.category)
+            && (self.description.is_empty() || self.description == transaction.description)
+    }
+
+    fn annual_limit(&self) -> Money {
+        match (self.monthly, self.annual) {
+            (Some(monthly), _) => (0..12).map(|_| monthly).sum(),
+            (None, Some(annual)) => annual,
+            (None, None) => Money::ZERO,
+        }
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.category)?;
+        if !self.description.is_empty() {
+            write!(f, " / {}", self.description)?;
+        }
+        write!(f, " ({})", if self.monthly.is_some() { "monthly" } else { "annual" })
+    }
+}
+
+/// How one budget stands in the year up to a date.
+#[derive(Debug, PartialEq)]
+pub struct BudgetStatus {
+    /// Label, limit and spending of each month so far for a monthly budget, or of the year so far
+    /// for an annual one.
+    pub periods: Vec<(String, Money, Money)>,
+    pub year_to_date: Money,
+    pub annual_limit: Money,
+    /// The year-to-date spending extended to the whole year at the same daily pace.
+    pub projected: Money,
+}
+
+fn check_budgets(budgets: &[Budget]) -> std::result::Result<(), BudgetError> {
+    for (index, budget) in budgets.iter().enumerate() {
+        if budget.monthly.is_some() == budget.annual.is_some() {
+            return Err(BudgetError::Limit { index });
+        }
+    }
+    Ok(())
+}
+
+/// Prints each budget's spending per month (or for the year so far) against its limit, flagging
+/// overruns, and projects the year-end total from the pace so far. The year is that of `as_of`,
+/// which defaults to the date of the latest transaction the policy keeps.
+pub fn print_budget_report(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    budgets_path: &Path,
+    as_of: Option<NaiveDate>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let budgets = read_budgets(budgets_path)?;
+    check_budgets(&budgets).map_err(|source| Error::Budget { path: budgets_path.to_path_buf(), source })?;
+    let sources = read_classified_transactions(input)?;
+    let as_of = match as_of.or_else(|| latest_date(&sources, policy)) {
+        Some(as_of) => as_of,
+        None => {
+            writeln!(out, "No transactions")?;
+            return Ok(());
+        }
+    };
+
+    writeln!(out, "Budgets for {} as of {}", as_of.year(), as_of)?;
+    for budget in &budgets {
+        let status = budget_status(budget, &sources, policy, as_of);
+        writeln!(out)?;
+        writeln!(out, "{}", budget)?;
+        for (label, limit, spent) in &status.periods {
+            let flag = if spent > limit { "  OVER" } else { "" };
+            writeln!(out, "  {:8} {:>10} of {:>10}{}", label, spent, limit, flag)?;
+        }
+        let flag = if status.projected > status.annual_limit { "  OVER" } else { "" };
+        writeln!(
+            out,
+            "  Year to date {} of {}, projected {} by year end{}",
+            status.year_to_date, status.annual_limit, status.projected, flag
+        )?;
+    }
+    Ok(())
+}
+
+fn latest_date(sources: &SourceTransactions, policy: &ExclusionPolicy) -> Option<NaiveDate> {
+    sources
+        .iter()
+        .flat_map(|(_source, transactions)| transactions)
+        .filter(|transaction| !policy.excludes(transaction))
+        .map(|transaction| transaction.date)
+        .max()
+}
+
+pub fn budget_status(
+    budget: &Budget,
+    sources: &SourceTransactions,
+    policy: &ExclusionPolicy,
+    as_of: NaiveDate,
+) -> BudgetStatus {
+    let year = as_of.year();
+    let mut spent_by_month = [Money::ZERO; 12];
+    for transaction in sources.iter().flat_map(|(_source, transactions)| transactions) {
+        if transaction.date.year() == year
+            && transaction.date <= as_of
+            && !policy.excludes(transaction)
+            && budget.applies_to(transaction)
+        {
+            spent_by_month[transaction.date.month0() as usize] -= transaction.amount;
+        }
+    }
+    let year_to_date: Money = spent_by_month.iter().sum();
+
+    let periods = match budget.monthly {
+        Some(monthly) => (0..=as_of.month0() as usize)
+            .map(|month0| (format!("{}-{:02}", year, month0 + 1), monthly, spent_by_month[month0]))
+            .collect(),
+        None => vec![(year.to_string(), budget.annual_limit(), year_to_date)],
+    };
+
+    let days_in_year = (NaiveDate::from_ymd(year + 1, 1, 1) - NaiveDate::from_ymd(year, 1, 1)).num_days();
+    let projected = year_to_date.percent(100.0 * days_in_year as f64 / as_of.ordinal() as f64);
+    BudgetStatus { periods, year_to_date, annual_limit: budget.annual_limit(), projected }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn transaction(month: u32, day: u32, category: &str, description: &str, cents: i64) -> Transaction {
+        Transaction {
+            description: description.to_string(),
+            category: category.to_string(),
+            ..test_transaction(NaiveDate::from_ymd(2023, month, day), "", cents)
+        }
+    }
+
+    fn budgets(json: &str) -> Vec<Budget> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn compares_monthly_spending_and_projects_year_end() {
+        let sources = vec![("Card".to_string(), vec![
+            transaction(1, 10, "Food/Groceries", "Safeway", -30000),
+            transaction(2, 10, "Food/Dining", "Cafe", -50000),
+            transaction(2, 20, "Food/Groceries", "Safeway", 5000),
+            transaction(3, 1, "Home", "Rent", -100000),
+            transaction(4, 1, "Food/Groceries", "Safeway", -99999),
+        ])];
+        let budget = &budgets(r#"[{"category": "Food", "monthly": 400}]"#)[0];
+
+        let status = budget_status(budget, &sources, &ExclusionPolicy::default(), NaiveDate::from_ymd(2023, 3, 31));
+
+        assert_eq!(status.periods, vec![
+            ("2023-01".to_string(), Money::from_cents(40000), Money::from_cents(30000)),
+            ("2023-02".to_string(), Money::from_cents(40000), Money::from_cents(45000)),
+            ("2023-03".to_string(), Money::from_cents(40000), Money::ZERO),
+        ]);
+        assert_eq!(status.year_to_date, Money::from_cents(75000));
+        assert_eq!(status.annual_limit, Money::from_cents(480000));
+        assert_eq!(status.projected, Money::from_cents(304167));
+    }
+
+    #[test]
+    fn narrows_annual_budget_to_description() {
+        let sources = vec![("Card".to_string(), vec![
+            transaction(6, 1, "Shopping", "Amazon", -20000),
+            transaction(6, 2, "Shopping", "Target", -10000),
+        ])];
+        let budget = &budgets(r#"[{"category": "Shopping", "description": "Amazon", "annual": "1,000"}]"#)[0];
+
+        let status = budget_status(budget, &sources, &ExclusionPolicy::default(), NaiveDate::from_ymd(2023, 12, 31));
+
+        assert_eq!(status.periods, vec![("2023".to_string(), Money::from_cents(100000), Money::from_cents(20000))]);
+        assert_eq!(status.projected, Money::from_cents(20000));
+    }
+
+    #[test]
+    fn rejects_budget_without_exactly_one_limit() {
+        assert!(check_budgets(&budgets(r#"[{"category": "Food", "monthly": 1}]"#)).is_ok());
+        assert!(matches!(
+            check_budgets(&budgets(r#"[{"category": "Food", "monthly": 1}, {"category": "Home"}]"#)),
+            Err(BudgetError::Limit { index: 1 })
+        ));
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+pub const CATEGORY_SEPARATOR: char = '/';
+
+/// Splits a category such as "Home/Utilities/Electric" into its path from the top level down,
+/// ignoring blank levels.
+pub fn category_path(category: &str) -> Vec<&str> {
+    category
+        .split(CATEGORY_SEPARATOR)
+        .map(str::trim)
+        .filter(|level| !level.is_empty())
+        .collect()
+}
+
+/// Truncates a category to at most `depth` levels, so "Home/Utilities/Electric" rolls up to
+/// "Home/Utilities" at depth 2 and to "Home" at depth 1.
+pub fn roll_up(category: &str, depth: usize) -> String {
+    let path = category_path(category);
+    path[..path.len().min(depth)].join(&CATEGORY_SEPARATOR.to_string())
+}
+
+/// Whether `category` is `ancestor` or one of its subcategories.
+pub fn is_within(category: &str, ancestor: &str) -> bool {
+    let ancestor_path = category_path(ancestor);
+    !ancestor_path.is_empty() && category_path(category).starts_with(&ancestor_path)
+}
+
+/// A category with the items filed directly under it and its subcategories by name.
+#[derive(Debug)]
+pub struct CategoryTree<T> {
+    pub items: Vec<T>,
+    pub subcategories: HashMap<String, CategoryTree<T>>,
+}
+
+impl<T> Default for CategoryTree<T> {
+    fn default() -> Self {
+        CategoryTree { items: vec![], subcategories: HashMap::new() }
+    }
+}
+
+impl<T> CategoryTree<T> {
+    /// Files `item` under `category`, creating the categories on its path as needed.
+    pub fn insert(&mut self, category: &str, item: T) {
+        let mut tree = self;
+        for level in category_path(category) {
+            tree = tree.subcategories.entry(level.to_string()).or_default();
+        }
+        tree.items.push(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_up_category_paths() {
+        assert_eq!(roll_up("Home/Utilities/Electric", 2), "Home/Utilities");
+        assert_eq!(roll_up("Home / Utilities", 1), "Home");
+        assert_eq!(roll_up("Groceries", 3), "Groceries");
+        assert!(is_within("Home/Utilities/Electric", "Home/Utilities"));
+        assert!(is_within("Home", "Home"));
+        assert!(!is_within("Homework", "Home"));
+        assert!(!is_within("Home", "Home/Utilities"));
+    }
+
+    #[test]
+    fn files_items_under_nested_categories() {
+        let mut tree = CategoryTree::default();
+        tree.insert("Home/Utilities/Electric", 1);
+        tree.insert("Home/Utilities", 2);
+        tree.insert("Home/Rent", 3);
+
+        let home = &tree.subcategories["Home"];
+        assert!(home.items.is_empty());
+        assert_eq!(home.subcategories.len(), 2);
+        assert_eq!(home.subcategories["Utilities"].items, vec![2]);
+        assert_eq!(home.subcategories["Utilities"].subcategories["Electric"].items, vec![1]);
+    }
+}
@@ -1,23 +1,34 @@
 #![allow(dead_code)]
 
 use std::collections::{BTreeMap, BTreeSet, HashMap};
-use std::path::PathBuf;
+use std::io::Write;
 
 use chrono::NaiveDate;
 
-use crate::file_io::{read_classification_rules, read_input};
-use crate::icicle_chart_data;
-use crate::transaction_classification::TransactionClassifier;
-use crate::transactions::csv_record_to_transaction;
+use crate::category_tree::roll_up;
+use crate::error::Result;
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::file_io::{
+    read_classified_transactions, read_classified_transactions_and_transfers, read_transactions,
+    InputOptions,
+};
+use crate::money::Money;
 
-pub fn print_draft_rules_for_unrecognized_descriptions() {
-    let (_grand_total, description_sums) = get_unrecognized_description_sums();
+type DescriptionSums = Vec<((String, String), Money)>;
 
-    println!("[");
+pub fn print_draft_rules_for_unrecognized_descriptions(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let (_grand_total, description_sums) = get_unrecognized_description_sums(input, policy)?;
+
+    writeln!(out, "[")?;
     for ((source, raw_description), sum) in &description_sums {
-        if (*sum).abs() < 90.0 { continue; }
+        if sum.abs() < Money::from_cents(9000) { continue; }
 
-        println!(
+        writeln!(
+            out,
             r#"  {{
     "sum": "{}",
     "source": "{}",
@@ -26,12 +37,13 @@ pub fn print_draft_rules_for_unrecognized_descriptions() {
     "category": "Unknown"
   }},"#,
             sum, source, raw_description, titlecase::titlecase(strip_unwanted_prefix(raw_description))
-        );
+        )?;
     }
-    println!("]");
+    writeln!(out, "]")?;
+    Ok(())
 }
 
-fn strip_unwanted_prefix(raw_description: &String) -> &str {
+fn strip_unwanted_prefix(raw_description: &str) -> &str {
     let unwanted_prefixes = vec!["SP ", "SQ *", "TST* "];
     for prefix in unwanted_prefixes
     {
@@ -43,74 +55,75 @@ fn strip_unwanted_prefix(raw_description: &String) -> &str {
     raw_description
 }
 
-pub fn sum_unrecognized_descriptions() {
-    let (grand_total, description_sums) = get_unrecognized_description_sums();
+pub fn sum_unrecognized_descriptions(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let (grand_total, description_sums) = get_unrecognized_description_sums(input, policy)?;
 
-    println!("Grand total: {:.2}", grand_total);
+    writeln!(out, "Grand total: {}", grand_total)?;
     for ((source, description), sum) in &description_sums {
-        println!("  {} / {}: {:.2}", source, description, sum);
+        writeln!(out, "  {} / {}: {}", source, description, sum)?;
     }
+    Ok(())
 }
 
-fn get_unrecognized_description_sums() -> (f32, Vec<((String, String), f32)>) {
-    let rules = read_classification_rules(PathBuf::from("input"));
-    let classifier = TransactionClassifier::new(rules);
-
-    let mut grand_total: f32 = 0.0;
-    let mut description_sums: HashMap<(String, String), f32> = HashMap::new();
-    for (source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
-        for csv_record in cvs_records {
-            let transaction = csv_record_to_transaction(&csv_record, &csv_config);
-            let transaction = classifier.classify_transaction(transaction);
-            if !icicle_chart_data::should_exclude_transaction(&transaction) && transaction.category == "Unknown" {
+fn get_unrecognized_description_sums(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+) -> Result<(Money, DescriptionSums)> {
+    let mut grand_total = Money::ZERO;
+    let mut description_sums: HashMap<(String, String), Money> = HashMap::new();
+    for (source, transactions) in read_classified_transactions(input)? {
+        for transaction in transactions {
+            if !policy.excludes(&transaction) && transaction.category == "Unknown" {
                 grand_total += transaction.amount;
-                *description_sums.entry((source.clone(), transaction.raw_description)).or_insert(0.0) += transaction.amount;
+                *description_sums.entry((source.clone(), transaction.raw_description)).or_default() += transaction.amount;
             }
         }
     }
 
-    let mut description_sums: Vec<((String, String), f32)> = description_sums
+    let mut description_sums: DescriptionSums = description_sums
         .iter()
         .map(|(desc, amt)| (desc.clone(), *amt))
         .collect();
-    description_sums.sort_by(|(_desc1, amt1), (_desc2, amt2)| amt1.partial_cmp(amt2).unwrap());
-    (grand_total, description_sums)
+    description_sums.sort_by_key(|(_desc, amt)| *amt);
+    Ok((grand_total, description_sums))
 }
 
-pub fn list_unrecognized_descriptions() {
-    let raw_descriptions = get_unrecognized_descriptions();
+pub fn list_unrecognized_descriptions(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let raw_descriptions = get_unrecognized_descriptions(input, policy)?;
 
     for (source, raw_description) in &raw_descriptions {
-        println!("{}: {}", source, raw_description);
+        writeln!(out, "{}: {}", source, raw_description)?;
     }
+    Ok(())
 }
 
-fn get_unrecognized_descriptions() -> BTreeSet<(String, String)> {
-    let rules = read_classification_rules(PathBuf::from("input"));
-    let classifier = TransactionClassifier::new(rules);
-
+fn get_unrecognized_descriptions(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+) -> Result<BTreeSet<(String, String)>> {
     let mut raw_descriptions = BTreeSet::new();
-    for (source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
-        for csv_record in cvs_records {
-            let transaction = csv_record_to_transaction(&csv_record, &csv_config);
-            let transaction = classifier.classify_transaction(transaction);
-            if !icicle_chart_data::should_exclude_transaction(&transaction) && transaction.category == "Unknown" {
+    for (source, transactions) in read_classified_transactions(input)? {
+        for transaction in transactions {
+            if !policy.excludes(&transaction) && transaction.category == "Unknown" {
                 raw_descriptions.insert((source.clone(), transaction.raw_description));
             }
         }
     }
-    raw_descriptions
+    Ok(raw_descriptions)
 }
 
-pub fn list_descriptions() {
-    let rules = read_classification_rules(PathBuf::from("input"));
-    let classifier = TransactionClassifier::new(rules);
-
+pub fn list_descriptions(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
     let mut descriptions = BTreeMap::new();
-    for (_source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
-        for csv_record in cvs_records {
-            let transaction = csv_record_to_transaction(&csv_record, &csv_config);
-            let transaction = classifier.classify_transaction(transaction);
+    for (_source, transactions) in read_classified_transactions(input)? {
+        for transaction in transactions {
             if transaction.date >= NaiveDate::from_ymd(2020, 3, 14) {
                 descriptions.insert(transaction.raw_description, transaction.description);
             }
@@ -118,16 +131,16 @@ pub fn list_descriptions() {
     }
 
     for (raw_description, description) in &descriptions {
-        println!("\"{}\" => \"{}\"", raw_description, description);
+        writeln!(out, "\"{}\" => \"{}\"", raw_description, description)?;
     }
+    Ok(())
 }
 
-pub fn print_draft_rules() {
+pub fn print_draft_rules(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
     let mut descriptions = BTreeMap::new();
-    for (source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
+    for (source, transactions) in read_transactions(input)? {
         if source == "PayPal" {
-            for csv_record in cvs_records {
-                let transaction = csv_record_to_transaction(&csv_record, &csv_config);
+            for transaction in transactions {
                 if transaction.date >= NaiveDate::from_ymd(2020, 3, 14) {
                     descriptions.insert(transaction.raw_description, transaction.description);
                 }
@@ -135,26 +148,27 @@ pub fn print_draft_rules() {
         }
     }
 
-    println!("[");
-    for (raw_description, _description) in &descriptions {
-        println!(
+    writeln!(out, "[")?;
+    for raw_description in descriptions.keys() {
+        writeln!(
+            out,
             r#"  {{
     "raw_prefix": "{}",
     "description": "{}",
     "category": "TODO"
   }},"#,
             raw_description, raw_description
-        );
+        )?;
     }
-    println!("]");
+    writeln!(out, "]")?;
+    Ok(())
 }
 
-pub fn align_checking_and_paypal() {
+pub fn align_checking_and_paypal(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
     let mut tuples = vec![];
-    for (source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
+    for (source, transactions) in read_transactions(input)? {
         if source == "PayPal" || source == "WSECU Checking" {
-            for csv_record in cvs_records {
-                let transaction = csv_record_to_transaction(&csv_record, &csv_config);
+            for transaction in transactions {
                 if transaction.date >= NaiveDate::from_ymd(2020, 3, 14)
                     && transaction.date < NaiveDate::from_ymd(2021, 3, 14)
                 {
@@ -173,53 +187,85 @@ pub fn align_checking_and_paypal() {
     });
 
     for (date, description, amount) in tuples {
-        println!("{}, \"{}\", {}", date, description, amount);
+        writeln!(out, "{}, \"{}\", {}", date, description, amount)?;
     }
+    Ok(())
 }
 
-pub fn print_categories() {
-    let rules = read_classification_rules(PathBuf::from("input"));
-    let classifier = TransactionClassifier::new(rules);
-
+pub fn print_categories(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
     let mut categories = BTreeSet::new();
-    for (_source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
-        for csv_record in cvs_records {
-            let transaction = classifier
-                .classify_transaction(csv_record_to_transaction(&csv_record, &csv_config));
+    for (_source, transactions) in read_classified_transactions(input)? {
+        for transaction in transactions {
             categories.insert(transaction.category);
         }
     }
 
     for category in categories {
-        println!("{}", category);
+        writeln!(out, "{}", category)?;
     }
+    Ok(())
 }
 
-pub fn sum_categories() {
-    let rules = read_classification_rules(PathBuf::from("input"));
-    let classifier = TransactionClassifier::new(rules);
-
-    let mut grand_total: f32 = 0.0;
-    let mut category_sums: HashMap<String, f32> = HashMap::new();
-    for (_source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
-        for csv_record in cvs_records {
-            let transaction = classifier
-                .classify_transaction(csv_record_to_transaction(&csv_record, &csv_config));
-            if !icicle_chart_data::should_exclude_transaction(&transaction) {
+/// Prints the total for each category, rolling subcategories up into their parent categories
+/// below `depth` levels if given.
+pub fn sum_categories(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    depth: Option<usize>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut grand_total = Money::ZERO;
+    let mut category_sums: HashMap<String, Money> = HashMap::new();
+    for (_source, transactions) in read_classified_transactions(input)? {
+        for transaction in transactions {
+            if !policy.excludes(&transaction) {
+                let category = match depth {
+                    Some(depth) => roll_up(&transaction.category, depth),
+                    None => transaction.category,
+                };
                 grand_total += transaction.amount;
-                *category_sums.entry(transaction.category).or_insert(0.0) += transaction.amount;
+                *category_sums.entry(category).or_default() += transaction.amount;
             }
         }
     }
 
-    let mut category_sums: Vec<(String, f32)> = category_sums
+    let mut category_sums: Vec<(String, Money)> = category_sums
         .iter()
         .map(|(cat, amt)| (cat.clone(), *amt))
         .collect();
-    category_sums.sort_by(|(_cat1, amt1), (_cat2, amt2)| amt1.partial_cmp(amt2).unwrap());
+    category_sums.sort_by_key(|(_cat, amt)| *amt);
 
-    println!("Grand total: {:.2}", grand_total);
+    writeln!(out, "Grand total: {}", grand_total)?;
     for (category, sum) in &category_sums {
-        println!("  {}: {:.2}", category, sum);
+        writeln!(out, "  {}: {}", category, sum)?;
+    }
+    Ok(())
+}
+
+pub fn print_transfers(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
+    let (_sources, transfers) = read_classified_transactions_and_transfers(input)?;
+
+    writeln!(out, "Matched transfers:")?;
+    for pair in &transfers.pairs {
+        writeln!(
+            out,
+            "  {}: {} {} \"{}\" -> {} {} \"{}\"",
+            -pair.outflow.amount,
+            pair.outflow.date,
+            pair.outflow_source,
+            pair.outflow.raw_description,
+            pair.inflow.date,
+            pair.inflow_source,
+            pair.inflow.raw_description
+        )?;
+    }
+    writeln!(out, "Unmatched transfer candidates:")?;
+    for (source, transaction) in &transfers.unmatched {
+        writeln!(
+            out,
+            "  {}: {} {} \"{}\"",
+            transaction.amount, transaction.date, source, transaction.raw_description
+        )?;
     }
+    Ok(())
 }
@@ -0,0 +1,116 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+
+use crate::money::Money;
+use crate::transactions::Transaction;
+
+/// A transaction dropped because an earlier file of the same source already contained it.
+#[derive(Debug)]
+pub struct Duplicate {
+    pub source: String,
+    pub path: PathBuf,
+    pub transaction: Transaction,
+}
+
+type TransactionKey = (NaiveDate, Money, String);
+
+/// Merges the files of one source, dropping transactions that repeat ones from earlier files.
+/// Identical transactions within a single file are genuine repeats (two coffees on the same day),
+/// so a transaction is kept only if its occurrence count within its own file exceeds the largest
+/// count for the same date, amount and raw description in any earlier file. Transactions with a
+/// bank ID are instead kept only the first time their ID appears.
+pub fn deduplicate(
+    source: &str,
+    files: Vec<(PathBuf, Vec<Transaction>)>,
+) -> (Vec<Transaction>, Vec<Duplicate>) {
+    let mut kept = vec![];
+    let mut duplicates = vec![];
+    let mut max_counts: HashMap<TransactionKey, usize> = HashMap::new();
+    let mut bank_ids = HashSet::new();
+    for (path, transactions) in files {
+        let mut file_counts: HashMap<TransactionKey, usize> = HashMap::new();
+        for transaction in transactions {
+            let is_new = if transaction.bank_id.is_empty() {
+                let key = transaction_key(&transaction);
+                let max_count = max_counts.get(&key).copied().unwrap_or(0);
+                let file_count = file_counts.entry(key).or_insert(0);
+                *file_count += 1;
+                *file_count > max_count
+            } else {
+                bank_ids.insert(transaction.bank_id.clone())
+            };
+            if is_new {
+                kept.push(transaction);
+            } else {
+                duplicates.push(Duplicate {
+                    source: source.to_string(),
+                    path: path.clone(),
+                    transaction,
+                });
+            }
+        }
+        for (key, file_count) in file_counts {
+            let max_count = max_counts.entry(key).or_insert(0);
+            *max_count = (*max_count).max(file_count);
+        }
+    }
+    (kept, duplicates)
+}
+
+fn transaction_key(transaction: &Transaction) -> TransactionKey {
+    (transaction.date, transaction.amount, transaction.raw_description.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn transaction(day: u32, raw_description: &str, cents: i64) -> Transaction {
+        test_transaction(NaiveDate::from_ymd(2023, 1, day), raw_description, cents)
+    }
+
+    #[test]
+    fn drops_transactions_repeated_in_overlapping_files() {
+        let (kept, duplicates) = deduplicate("Card", vec![
+            (PathBuf::from("jan.csv"), vec![transaction(1, "ACME", -100), transaction(31, "SAFEWAY", -200)]),
+            (PathBuf::from("feb.csv"), vec![transaction(31, "SAFEWAY", -200), transaction(2, "ACME", -100)]),
+        ]);
+
+        assert_eq!(kept.len(), 3);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].path, PathBuf::from("feb.csv"));
+        assert_eq!(duplicates[0].transaction.raw_description, "SAFEWAY");
+    }
+
+    #[test]
+    fn keeps_genuine_same_day_repeats() {
+        let (kept, duplicates) = deduplicate("Card", vec![
+            (PathBuf::from("a.csv"), vec![transaction(5, "COFFEE", -450), transaction(5, "COFFEE", -450)]),
+            (PathBuf::from("b.csv"), vec![
+                transaction(5, "COFFEE", -450),
+                transaction(5, "COFFEE", -450),
+                transaction(5, "COFFEE", -450),
+            ]),
+        ]);
+
+        assert_eq!(kept.len(), 3);
+        assert_eq!(duplicates.len(), 2);
+    }
+
+    #[test]
+    fn deduplicates_by_bank_id_when_present() {
+        let with_id = |bank_id: &str| Transaction { bank_id: bank_id.to_string(), ..transaction(5, "COFFEE", -450) };
+        let (kept, duplicates) = deduplicate("Card", vec![
+            (PathBuf::from("a.ofx"), vec![with_id("1")]),
+            (PathBuf::from("b.ofx"), vec![with_id("1"), with_id("2")]),
+        ]);
+
+        assert_eq!(kept.len(), 2);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].transaction.bank_id, "1");
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use csv::StringRecord;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::encoding::{decode, Encoding};
+use crate::error::{Error, Result};
+use crate::transactions::parse_amount;
+
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// Tried in order, so month/day wins over day/month when both fit.
+const DATE_FORMATS: [&str; 12] = [
+    "%m/%d/%Y", "%m/%d/%y", "%Y-%m-%d", "%Y/%m/%d", "%d/%m/%Y", "%d/%m/%y", "%d.%m.%Y", "%m-%d-%Y",
+    "%d-%m-%Y", "%Y%m%d", "%b %d, %Y", "%d %b %Y",
+];
+
+/// Share of rows after the header a date column must parse, leaving room for summary rows.
+const DATE_THRESHOLD: f64 = 0.5;
+
+#[derive(Debug)]
+pub enum DetectError {
+    Table,
+    DateColumn,
+    AmountColumn,
+    DescriptionColumn,
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DetectError::Table => write!(f, "no rows with a consistent number of columns"),
+            DetectError::DateColumn => write!(f, "no column of dates in a known format"),
+            DetectError::AmountColumn => write!(f, "no column of amounts"),
+            DetectError::DescriptionColumn => write!(f, "no column of descriptions"),
+        }
+    }
+}
+
+impl std::error::Error for DetectError {}
+
+/// A config.json for a new source, to be checked before use.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DraftConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thousands_separator: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decimal_separator: Option<char>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub skip_lines: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub footer_lines: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_column: Option<String>,
+    pub date_format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_column: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_column: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_column: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debit_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debit_column: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credit_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credit_column: Option<String>,
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+/// Prints a draft config.json for the CSV file at `path`, found by sniffing its encoding,
+/// delimiter, header row, date column and format, description and category columns, amount
+/// columns and separators, and footer. Text that is not UTF-8 is taken to be Windows-1252.
+/// Warnings about the guesses go to stderr.
+pub fn print_draft_config(path: &Path, out: &mut dyn Write) -> Result<()> {
+    let bytes = fs::read(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+    let (contents, encoding) = match decode(&bytes, Encoding::Utf8) {
+        Ok(contents) => (contents, None),
+        Err(_) => (decode(&bytes, Encoding::Windows1252).unwrap(), Some(Encoding::Windows1252.name())),
+    };
+    let (mut draft, warnings) =
+        draft_config(&contents).map_err(|source| Error::Detect { path: path.to_path_buf(), source })?;
+    for warning in warnings {
+        eprintln!("warning: {}: {}", path.display(), warning);
+    }
+    draft.encoding = encoding;
+    writeln!(out, "{}", serde_json::to_string_pretty(&draft).unwrap())?;
+    Ok(())
+}
+
+/// A row and the 0-based line it starts on.
+type Row = (usize, StringRecord);
+
+/// Drafts a config.json for the CSV `contents`, with warnings about guesses worth checking.
+pub fn draft_config(contents: &str) -> std::result::Result<(DraftConfig, Vec<String>), DetectError> {
+    let mut warnings = vec![];
+    let (delimiter, rows) = DELIMITERS
+        .iter()
+        .map(|&delimiter| (delimiter, table_rows(contents, delimiter)))
+        .max_by_key(|(_delimiter, rows)| rows.len())
+        .filter(|(_delimiter, rows)| rows.len() >= 2)
+        .ok_or(DetectError::Table)?;
+
+    let (header_line, header) = &rows[0];
+    let body = &rows[1..];
+    let (date_index, date_format) = date_column(body).ok_or(DetectError::DateColumn)?;
+    if parse_date(&header[date_index], date_format).is_some() {
+        warnings.push("the first row looks like a transaction, but is read as the header".to_string());
+    }
+    let data: Vec<&Row> = body
+        .iter()
+        .filter(|(_line, row)| parse_date(&row[date_index], date_format).is_some())
+        .collect();
+    let last_data_line = data.last().map_or(*header_line, |(line, _row)| *line);
+
+    let names: Vec<String> = header.iter().map(|name| name.trim().to_lowercase()).collect();
+    let separators = amount_separators(&data, date_index);
+    let numeric: Vec<usize> = (0..header.len())
+        .filter(|&index| index != date_index && is_numeric_column(&data, index, separators))
+        .collect();
+    let mut draft = DraftConfig {
+        delimiter: Some(delimiter as char).filter(|delimiter| *delimiter != ','),
+        thousands_separator: Some(separators.0).filter(|_| separators != (',', '.')),
+        decimal_separator: Some(separators.1).filter(|_| separators != (',', '.')),
+        skip_lines: *header_line,
+        footer_lines: contents.lines().skip(last_data_line + 1).filter(|line| !line.trim().is_empty()).count(),
+        date_format: date_format.to_string(),
+        ..DraftConfig::default()
+    };
+    let use_names = header.iter().all(|name| !name.trim().is_empty())
+        && names.iter().collect::<HashSet<_>>().len() == names.len();
+    let column = |index: usize| -> (Option<usize>, Option<String>) {
+        if use_names {
+            (None, Some(header[index].trim().to_string()))
+        } else {
+            (Some(index), None)
+        }
+    };
+
+    (draft.date_index, draft.date_column) = column(date_index);
+    match amount_columns(&data, &names, &numeric).ok_or(DetectError::AmountColumn)? {
+        AmountColumns::Single(index) => (draft.amount_index, draft.amount_column) = column(index),
+        AmountColumns::DebitCredit(debit, credit) => {
+            (draft.debit_index, draft.debit_column) = column(debit);
+            (draft.credit_index, draft.credit_column) = column(credit);
+        }
+    }
+    let text: Vec<usize> = (0..header.len())
+        .filter(|index| *index != date_index && !numeric.contains(index))
+        .collect();
+    let description = description_column(&data, &names, &text).ok_or(DetectError::DescriptionColumn)?;
+    (draft.description_index, draft.description_column) = column(description);
+    if let Some(category) = text.iter().find(|&&index| index != description && names[index].contains("category")) {
+        (draft.category_index, draft.category_column) = column(*category);
+    }
+    Ok((draft, warnings))
+}
+
+/// The rows with the most common number of fields, when that is more than one. Lines are read
+/// one at a time, as fields spanning lines are rare and the csv crate miscounts CRLF lines.
+fn table_rows(contents: &str, delimiter: u8) -> Vec<Row> {
+    let rows: Vec<Row> = contents
+        .lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let mut reader =
+                csv::ReaderBuilder::new().has_headers(false).delimiter(delimiter).from_reader(text.as_bytes());
+            reader.records().next()?.ok().map(|row| (line, row))
+        })
+        .collect();
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for (_line, row) in &rows {
+        *counts.entry(row.len()).or_default() += 1;
+    }
+    let width = counts
+        .into_iter()
+        .filter(|(width, _count)| *width > 1)
+        .max_by_key(|&(width, count)| (count, width))
+        .map_or(0, |(width, _count)| width);
+    rows.into_iter().filter(|(_line, row)| row.len() == width).collect()
+}
+
+/// Rejects years before 1000, which is what `%Y` makes of a two-digit year.
+fn parse_date(value: &str, format: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), format).ok().filter(|date| date.year() >= 1000)
+}
+
+/// The first column and format parsing the most rows, if enough of them.
+fn date_column(rows: &[Row]) -> Option<(usize, &'static str)> {
+    let width = rows.first()?.1.len();
+    let mut best: Option<(usize, &'static str, usize)> = None;
+    for index in 0..width {
+        for format in DATE_FORMATS {
+            let parsed = rows.iter().filter(|(_line, row)| parse_date(&row[index], format).is_some()).count();
+            if best.is_none_or(|(_index, _format, best_parsed)| parsed > best_parsed) {
+                best = Some((index, format, parsed));
+            }
+        }
+    }
+    best.filter(|(_index, _format, parsed)| *parsed as f64 > DATE_THRESHOLD * rows.len() as f64)
+        .map(|(index, format, _parsed)| (index, format))
+}
+
+/// Thousands and decimal separators: "1.234,56" if more values end in a decimal comma than a
+/// decimal point, otherwise "1,234.56".
+fn amount_separators(rows: &[&Row], date_index: usize) -> (char, char) {
+    let decimal_comma = Regex::new(r"\d,\d{1,2}\)?$").unwrap();
+    let decimal_point = Regex::new(r"\d\.\d{1,2}\)?$").unwrap();
+    let (mut commas, mut points) = (0, 0);
+    for (_line, row) in rows {
+        for (index, value) in row.iter().enumerate() {
+            if index != date_index {
+                commas += decimal_comma.is_match(value.trim()) as usize;
+                points += decimal_point.is_match(value.trim()) as usize;
+            }
+        }
+    }
+    if commas > points { ('.', ',') } else { (',', '.') }
+}
+
+fn is_numeric_column(rows: &[&Row], index: usize, (thousands, decimal): (char, char)) -> bool {
+    let values: Vec<&str> = rows.iter().map(|(_line, row)| row[index].trim()).filter(|value| !value.is_empty()).collect();
+    !values.is_empty() && values.iter().all(|value| parse_amount(value, thousands, decimal).is_some())
+}
+
+enum AmountColumns {
+    Single(usize),
+    DebitCredit(usize, usize),
+}
+
+/// Debit and credit columns if named so, or if two columns are never both filled in; otherwise
+/// the amount column, preferring one named so and avoiding balances.
+fn amount_columns(rows: &[&Row], names: &[String], numeric: &[usize]) -> Option<AmountColumns> {
+    let named = |words: &[&str]| numeric.iter().copied().find(|&index| words.iter().any(|word| names[index].contains(word)));
+    if let (Some(debit), Some(credit)) = (named(&["debit", "withdrawal"]), named(&["credit", "deposit"])) {
+        return Some(AmountColumns::DebitCredit(debit, credit));
+    }
+    if let Some(amount) = named(&["amount"]) {
+        return Some(AmountColumns::Single(amount));
+    }
+    let candidates: Vec<usize> = numeric.iter().copied().filter(|&index| !names[index].contains("balance")).collect();
+    if let [first, second] = candidates[..] {
+        let filled = |row: &StringRecord, index: usize| !row[index].trim().is_empty();
+        if rows.iter().all(|(_line, row)| filled(row, first) != filled(row, second)) {
+            return Some(AmountColumns::DebitCredit(first, second));
+        }
+    }
+    candidates.first().map(|&index| AmountColumns::Single(index))
+}
+
+/// A column named like a description, or else the one with the most distinct and longest values.
+fn description_column(rows: &[&Row], names: &[String], text: &[usize]) -> Option<usize> {
+    for word in ["description", "payee", "merchant", "name", "details", "memo"] {
+        if let Some(&index) = text.iter().find(|&&index| names[index].contains(word)) {
+            return Some(index);
+        }
+    }
+    text.iter()
+        .copied()
+        .max_by_key(|&index| {
+            let values: HashSet<&str> = rows.iter().map(|(_line, row)| row[index].trim()).collect();
+            (values.len(), values.iter().map(|value| value.len()).sum::<usize>())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_named_columns_preamble_and_footer() {
+        let contents = "\
+Account,1234
+\"Trans. Date\",\"Post Date\",\"Payee\",\"Category\",\"Debit\",\"Credit\",\"Balance\"
+2023-01-05,2023-01-06,ACME FALAFEL,Dining,12.93,,987.07
+2023-01-07,2023-01-08,PAYROLL,Income,,1000.00,1987.07
+2023-01-09,2023-01-09,SQ *COFFEE,Dining,4.50,,1982.57
+Totals,,,,17.43,1000.00,
+";
+
+        let (draft, warnings) = draft_config(contents).unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(draft, DraftConfig {
+            skip_lines: 1,
+            footer_lines: 1,
+            date_column: Some("Trans. Date".to_string()),
+            date_format: "%Y-%m-%d".to_string(),
+            description_column: Some("Payee".to_string()),
+            category_column: Some("Category".to_string()),
+            debit_column: Some("Debit".to_string()),
+            credit_column: Some("Credit".to_string()),
+            ..DraftConfig::default()
+        });
+    }
+
+    #[test]
+    fn detects_indexes_and_single_amount_without_names() {
+        let contents = "\
+Date,,,
+01/31/2023,SAFEWAY 123,-80.25,x
+02/01/2023,CITI AUTOPAY,-200.00,y
+02/15/2023,SAFEWAY 123,-7.50,x
+";
+
+        let (draft, _warnings) = draft_config(contents).unwrap();
+
+        assert_eq!(draft.date_index, Some(0));
+        assert_eq!(draft.date_format, "%m/%d/%Y");
+        assert_eq!(draft.description_index, Some(1));
+        assert_eq!(draft.amount_index, Some(2));
+        assert!(draft_config("just one line").is_err());
+    }
+
+    #[test]
+    fn detects_two_digit_years() {
+        let contents = "Date,Description,Amount
+01/31/23,SAFEWAY 123,-80.25
+02/01/23,CITI AUTOPAY,-200.00
+";
+
+        assert_eq!(draft_config(contents).unwrap().0.date_format, "%m/%d/%y");
+    }
+
+    #[test]
+    fn warns_when_the_header_looks_like_a_transaction() {
+        let contents = "\
+01/31/2023,SAFEWAY 123,-80.25
+02/01/2023,CITI AUTOPAY,-200.00
+02/15/2023,SAFEWAY 123,-7.50
+";
+
+        let (draft, warnings) = draft_config(contents).unwrap();
+
+        assert_eq!(draft.skip_lines, 0);
+        assert_eq!(warnings, vec!["the first row looks like a transaction, but is read as the header"]);
+    }
+
+    #[test]
+    fn detects_semicolons_and_decimal_commas() {
+        let contents = "\
+Buchungstag;Verwendungszweck;Betrag
+01.02.2023;B\u{e4}ckerei;-1.234,56
+02.02.2023;Gehalt;2.500,00
+";
+
+        let (draft, _warnings) = draft_config(contents).unwrap();
+
+        assert_eq!(draft.delimiter, Some(';'));
+        assert_eq!(draft.thousands_separator, Some('.'));
+        assert_eq!(draft.decimal_separator, Some(','));
+        assert_eq!(draft.date_format, "%d.%m.%Y");
+        assert_eq!(draft.amount_column, Some("Betrag".to_string()));
+        assert_eq!(draft.description_column, Some("Verwendungszweck".to_string()));
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const UTF16LE_BOM: &[u8] = b"\xFF\xFE";
+const UTF16BE_BOM: &[u8] = b"\xFE\xFF";
+
+/// Characters of Windows-1252 from 0x80 to 0x9F, where it differs from Latin-1. Unassigned bytes
+/// keep their Latin-1 meaning.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// The character encoding of a source's files. A byte order mark, if any, is removed and, for
+/// UTF-16, decides the byte order; UTF-16 files are recognized by it even when UTF-8 is set.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    #[serde(rename = "latin-1", alias = "latin1", alias = "iso-8859-1")]
+    Latin1,
+    #[serde(rename = "windows-1252", alias = "cp1252")]
+    Windows1252,
+    /// Little-endian unless a byte order mark says otherwise.
+    #[serde(rename = "utf-16", alias = "utf16")]
+    Utf16,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "latin-1",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Utf16 => "utf-16",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EncodingError {
+    pub encoding: Encoding,
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "file is not valid {}; set \"encoding\" in config.json", self.encoding.name())
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<String, EncodingError> {
+    let encoding = match encoding {
+        Encoding::Utf8 | Encoding::Utf16 if bytes.starts_with(UTF16LE_BOM) => Encoding::Utf16Le,
+        Encoding::Utf8 | Encoding::Utf16 if bytes.starts_with(UTF16BE_BOM) => Encoding::Utf16Be,
+        Encoding::Utf16 => Encoding::Utf16Le,
+        encoding => encoding,
+    };
+    let decoded = match encoding {
+        Encoding::Utf8 => {
+            let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+            String::from_utf8(bytes.to_vec()).map_err(|_| EncodingError { encoding })?
+        }
+        Encoding::Latin1 => bytes.iter().map(|&byte| byte as char).collect(),
+        Encoding::Windows1252 => bytes
+            .iter()
+            .map(|&byte| match byte {
+                0x80..=0x9F => WINDOWS_1252_HIGH[byte as usize - 0x80],
+                _ => byte as char,
+            })
+            .collect(),
+        Encoding::Utf16Le | Encoding::Utf16Be | Encoding::Utf16 => {
+            if !bytes.len().is_multiple_of(2) {
+                return Err(EncodingError { encoding });
+            }
+            let units: Vec<u16> = bytes
+                .chunks(2)
+                .map(|pair| match encoding {
+                    Encoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+                    _ => u16::from_le_bytes([pair[0], pair[1]]),
+                })
+                .collect();
+            String::from_utf16(&units).map_err(|_| EncodingError { encoding })?
+        }
+    };
+    Ok(decoded.strip_prefix('\u{FEFF}').map(str::to_string).unwrap_or(decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_single_byte_encodings_and_strips_utf8_bom() {
+        assert_eq!(decode(b"\xEF\xBB\xBFCaf\xC3\xA9", Encoding::Utf8).unwrap(), "Café");
+        assert!(decode(b"Caf\xE9", Encoding::Utf8).is_err());
+        assert_eq!(decode(b"Caf\xE9", Encoding::Latin1).unwrap(), "Café");
+        assert_eq!(decode(b"\x80 5", Encoding::Windows1252).unwrap(), "€ 5");
+    }
+
+    #[test]
+    fn decodes_utf16_by_byte_order_mark() {
+        let little_endian = b"\xFF\xFEA\x00\xE9\x00";
+        let big_endian = b"\xFE\xFF\x00A\x00\xE9";
+
+        assert_eq!(decode(little_endian, Encoding::Utf8).unwrap(), "Aé");
+        assert_eq!(decode(big_endian, Encoding::Utf16).unwrap(), "Aé");
+        assert_eq!(decode(b"\x00A", Encoding::Utf16Be).unwrap(), "A");
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::budgets::BudgetError;
+use crate::detect::DetectError;
+use crate::ofx::OfxError;
+use crate::overrides::OverrideError;
+use crate::qif::QifError;
+use crate::transaction_classification::RuleError;
+use crate::transactions::{LayoutError, RecordError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io { path: PathBuf, source: io::Error },
+    FileName { path: PathBuf },
+    Config { path: PathBuf, source: serde_json::Error },
+    Rules { path: PathBuf, source: serde_json::Error },
+    Rule { path: PathBuf, source: RuleError },
+    Exclusions { path: PathBuf, source: serde_json::Error },
+    Overrides { path: PathBuf, source: serde_json::Error },
+    Override { path: PathBuf, source: OverrideError },
+    Budgets { path: PathBuf, source: serde_json::Error },
+    Budget { path: PathBuf, source: BudgetError },
+    Csv { source_name: String, path: PathBuf, source: csv::Error },
+    Layout { source_name: String, path: PathBuf, source: LayoutError },
+    Record { source_name: String, path: PathBuf, line: u64, source: RecordError },
+    Detect { path: PathBuf, source: DetectError },
+    Ofx { source_name: String, path: PathBuf, source: OfxError },
+    Qif { source_name: String, path: PathBuf, source: QifError },
+    Ledger { path: PathBuf, line: u64, source: serde_json::Error },
+    Periods(String),
+    Output(io::Error),
+    Serve { address: String, source: Box<dyn std::error::Error + Send + Sync> },
+    Multiple(Vec<Error>),
+}
+
+impl Error {
+    /// Folds a list of collected problems into a single error, or `Ok` if there were none.
+    pub fn from_all(mut errors: Vec<Error>) -> Result<()> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.pop().unwrap()),
+            _ => Err(Error::Multiple(errors)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::FileName { path } => write!(f, "{}: file name is not valid Unicode", path.display()),
+            Error::Config { path, source } => {
+                write!(f, "{}: invalid CSV config: {}", path.display(), source)
+            }
+            Error::Rules { path, source } => {
+                write!(f, "{}: invalid classification rules: {}", path.display(), source)
+            }
+            Error::Rule { path, source } => {
+                write!(f, "{}: invalid classification rule: {}", path.display(), source)
+            }
+            Error::Exclusions { path, source } => {
+                write!(f, "{}: invalid exclusion policy: {}", path.display(), source)
+            }
+            Error::Overrides { path, source } => {
+                write!(f, "{}: invalid overrides: {}", path.display(), source)
+            }
+            Error::Override { path, source } => {
+                write!(f, "{}: invalid override: {}", path.display(), source)
+            }
+            Error::Budgets { path, source } => {
+                write!(f, "{}: invalid budgets: {}", path.display(), source)
+            }
+            Error::Budget { path, source } => {
+                write!(f, "{}: invalid budget: {}", path.display(), source)
+            }
+            Error::Csv { source_name, path, source } => {
+                write!(f, "{} ({}): {}", path.display(), source_name, source)
+            }
+            Error::Layout { source_name, path, source } => {
+                write!(f, "{} ({}): {}", path.display(), source_name, source)
+            }
+            Error::Record { source_name, path, line, source } => {
+                write!(f, "{}:{} ({}): {}", path.display(), line, source_name, source)
+            }
+            Error::Detect { path, source } => {
+                write!(f, "{}: cannot detect CSV layout: {}", path.display(), source)
+            }
+            Error::Ofx { source_name, path, source } => {
+                write!(f, "{} ({}): {}", path.display(), source_name, source)
+            }
+            Error::Qif { source_name, path, source } => {
+                write!(f, "{} ({}): {}", path.display(), source_name, source)
+            }
+            Error::Ledger { path, line, source } => {
+                write!(f, "{}:{}: invalid ledger entry: {}", path.display(), line, source)
+            }
+            Error::Periods(message) => write!(f, "invalid periods: {}", message),
+            Error::Output(source) => write!(f, "cannot write output: {}", source),
+            Error::Serve { address, source } => write!(f, "cannot serve on {}: {}", address, source),
+            Error::Multiple(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Config { source, .. } => Some(source),
+            Error::Rules { source, .. } => Some(source),
+            Error::Rule { source, .. } => Some(source),
+            Error::Exclusions { source, .. } => Some(source),
+            Error::Overrides { source, .. } => Some(source),
+            Error::Override { source, .. } => Some(source),
+            Error::Budgets { source, .. } => Some(source),
+            Error::Budget { source, .. } => Some(source),
+            Error::Csv { source, .. } => Some(source),
+            Error::Layout { source, .. } => Some(source),
+            Error::Record { source, .. } => Some(source),
+            Error::Detect { source, .. } => Some(source),
+            Error::Ofx { source, .. } => Some(source),
+            Error::Qif { source, .. } => Some(source),
+            Error::Ledger { source, .. } => Some(source),
+            Error::Output(source) => Some(source),
+            Error::Serve { source, .. } => Some(source.as_ref()),
+            Error::FileName { .. } | Error::Periods(_) | Error::Multiple(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Output(error)
+    }
+}
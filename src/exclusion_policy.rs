@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer};
+
+use crate::category_tree::is_within;
+use crate::transactions::Transaction;
+
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Decides which transactions the reports leave out. Read from exclusions.json, e.g.
+/// `{"start_date": "2023-01-01", "end_date": "2024-01-01", "excluded_categories": ["Transfer"],
+/// "excluded_positive_categories": ["Travel"]}`. The start date is inclusive, the end date
+/// exclusive. Omitted fields, and a missing exclusions.json, take the defaults below.
+#[derive(Debug, Deserialize)]
+pub struct ExclusionPolicy {
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub start_date: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub end_date: Option<NaiveDate>,
+    #[serde(default = "default_excluded_categories")]
+    pub excluded_categories: HashSet<String>,
+    #[serde(default = "default_excluded_positive_categories")]
+    pub excluded_positive_categories: HashSet<String>,
+    #[serde(default)]
+    pub excluded_negative_categories: HashSet<String>,
+}
+
+impl Default for ExclusionPolicy {
+    fn default() -> Self {
+        ExclusionPolicy {
+            start_date: None,
+            end_date: None,
+            excluded_categories: default_excluded_categories(),
+            excluded_positive_categories: default_excluded_positive_categories(),
+            excluded_negative_categories: HashSet::new(),
+        }
+    }
+}
+
+impl ExclusionPolicy {
+    pub fn excludes(&self, transaction: &Transaction) -> bool {
+        if transaction.excluded {
+            return true;
+        }
+        if let Some(start_date) = self.start_date {
+            if transaction.date < start_date {
+                return true;
+            }
+        }
+        if let Some(end_date) = self.end_date {
+            if transaction.date >= end_date {
+                return true;
+            }
+        }
+        if contains_category(&self.excluded_categories, &transaction.category) {
+            return true;
+        }
+        if transaction.amount.is_positive() && contains_category(&self.excluded_positive_categories, &transaction.category) {
+            return true;
+        }
+        if transaction.amount.is_negative() && contains_category(&self.excluded_negative_categories, &transaction.category) {
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Whether the category or one of its parent categories is in `categories`.
+fn contains_category(categories: &HashSet<String>, category: &str) -> bool {
+    categories.iter().any(|excluded_category| is_within(category, excluded_category))
+}
+
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT)
+        .map_err(|error| format!("invalid date \"{}\" (expected YYYY-MM-DD): {}", value, error))
+}
+
+pub fn deserialize_date<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    parse_date(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+pub fn deserialize_optional_date<'de, D>(deserializer: D) -> Result<Option<NaiveDate>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => parse_date(&value).map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+fn default_excluded_categories() -> HashSet<String> {
+    to_string_set(&["Dividend", "Investment", "Reimbursed", "Salary", "Tax", "Transfer"])
+}
+
+fn default_excluded_positive_categories() -> HashSet<String> {
+    to_string_set(&["Travel", "Unknown"])
+}
+
+fn to_string_set(values: &[&str]) -> HashSet<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn transaction(date: NaiveDate, cents: i64, category: &str) -> Transaction {
+        Transaction { category: category.to_string(), ..test_transaction(date, "", cents) }
+    }
+
+    #[test]
+    fn excludes_transactions_outside_date_window() {
+        let policy: ExclusionPolicy =
+            serde_json::from_str(r#"{"start_date": "2023-01-01", "end_date": "2024-01-01"}"#).unwrap();
+
+        assert!(policy.excludes(&transaction(NaiveDate::from_ymd(2022, 12, 31), -100, "Food")));
+        assert!(!policy.excludes(&transaction(NaiveDate::from_ymd(2023, 1, 1), -100, "Food")));
+        assert!(!policy.excludes(&transaction(NaiveDate::from_ymd(2023, 12, 31), -100, "Food")));
+        assert!(policy.excludes(&transaction(NaiveDate::from_ymd(2024, 1, 1), -100, "Food")));
+    }
+
+    #[test]
+    fn excludes_categories_by_sign() {
+        let policy: ExclusionPolicy = serde_json::from_str(
+            r#"{
+                "excluded_categories": ["Transfer"],
+                "excluded_positive_categories": ["Travel"],
+                "excluded_negative_categories": ["Salary"]
+            }"#,
+        )
+            .unwrap();
+        let date = NaiveDate::from_ymd(2023, 6, 1);
+
+        assert!(policy.excludes(&transaction(date, -1000, "Transfer")));
+        assert!(policy.excludes(&transaction(date, 1000, "Transfer")));
+        assert!(policy.excludes(&transaction(date, 1000, "Travel")));
+        assert!(!policy.excludes(&transaction(date, -1000, "Travel")));
+        assert!(policy.excludes(&transaction(date, -1000, "Salary")));
+        assert!(!policy.excludes(&transaction(date, 1000, "Salary")));
+        assert!(!policy.excludes(&transaction(date, -1000, "Food")));
+        assert!(policy.excludes(&transaction(date, 1000, "Travel/Airfare")));
+        assert!(!policy.excludes(&transaction(date, 1000, "Travelling")));
+    }
+
+    #[test]
+    fn omitted_fields_take_the_same_defaults_as_a_missing_file() {
+        let policy: ExclusionPolicy = serde_json::from_str(r#"{"excluded_negative_categories": ["Gifts"]}"#).unwrap();
+        let default = ExclusionPolicy::default();
+
+        assert_eq!(policy.excluded_categories, default.excluded_categories);
+        assert_eq!(policy.excluded_positive_categories, default.excluded_positive_categories);
+        assert!(policy.excludes(&transaction(NaiveDate::from_ymd(2023, 6, 1), -1000, "Transfer")));
+    }
+
+    #[test]
+    fn rejects_malformed_date() {
+        let result: Result<ExclusionPolicy, _> = serde_json::from_str(r#"{"start_date": "1/1/2023"}"#);
+
+        assert!(result.is_err());
+    }
+}
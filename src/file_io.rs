@@ -1,101 +1,376 @@
-use std::fmt::{Debug, Display};
 use std::fs;
 use std::fs::DirEntry;
 use std::path::{Path, PathBuf};
-use std::process::exit;
 
-use csv::StringRecord;
+use crate::budgets::Budget;
+use crate::deduplication::{deduplicate, Duplicate};
+use crate::error::{Error, Result};
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::ledger::read_merged;
+use crate::ofx::parse_ofx;
+use crate::overrides::{transaction_ids, TransactionOverride, TransactionOverrides};
+use crate::qif::parse_qif;
+use crate::splits::split_transaction;
+use crate::transaction_classification::{TransactionClassificationRule, TransactionClassifier};
+use crate::transactions::{csv_record_to_transaction, CsvConfig, InputFormat, RecordError, Transaction};
+use crate::transfer_matching::{match_transfers, TransferMatches};
 
-use crate::transaction_classification::TransactionClassificationRule;
-use crate::transactions::CsvConfig;
+pub struct InputOptions {
+    pub input_dir: PathBuf,
+    pub rules_path: PathBuf,
+    pub overrides_path: Option<PathBuf>,
+    /// When set, transactions come from the ledger plus any input files not imported into it.
+    pub ledger_path: Option<PathBuf>,
+    pub transfer_day_window: i64,
+}
+
+pub type SourceInput = (String, CsvConfig, Vec<PathBuf>);
+
+pub type SourceTransactions = Vec<(String, Vec<Transaction>)>;
+
+/// The transactions of each CSV file of a source, in file name order.
+pub type SourceFiles = (String, Vec<(PathBuf, Vec<Transaction>)>);
+
+/// Reads and classifies the transactions in the input directory, applying manual overrides,
+/// splitting transactions into one transaction per part and categorizing matched transfers
+/// between sources as transfers. Warns about overrides that no longer match any transaction.
+pub fn read_classified_transactions(input: &InputOptions) -> Result<SourceTransactions> {
+    read_classified_transactions_and_transfers(input).map(|(sources, _transfers)| sources)
+}
+
+/// Like `read_classified_transactions`, but also returns the transfer matching results.
+pub fn read_classified_transactions_and_transfers(
+    input: &InputOptions,
+) -> Result<(SourceTransactions, TransferMatches)> {
+    let classifier = TransactionClassifier::new(read_classification_rules(&input.rules_path)?)
+        .map_err(|source| Error::Rule { path: input.rules_path.clone(), source })?;
+    let overrides = match &input.overrides_path {
+        Some(path) => TransactionOverrides::new(read_overrides(path)?)
+            .map_err(|source| Error::Override { path: path.clone(), source })?,
+        None => TransactionOverrides::default(),
+    };
+    let mut matched_overrides = vec![];
+    let mut sources: SourceTransactions = vec![];
+    for (source, transactions) in read_transactions(input)? {
+        let ids = transaction_ids(&source, &transactions);
+        let mut classified = vec![];
+        for (transaction, id) in transactions.into_iter().zip(ids) {
+            let (mut transaction, mut splits) = classifier.classify_transaction(&source, transaction);
+            if let Some((index, transaction_override)) = overrides.find(&id) {
+                transaction_override.apply(&mut transaction);
+                if let Some(override_splits) = &transaction_override.splits {
+                    splits = override_splits;
+                }
+                matched_overrides.push(index);
+            }
+            classified.extend(split_transaction(transaction, splits));
+        }
+        sources.push((source, classified));
+    }
+    for transaction_override in overrides.unmatched(&matched_overrides) {
+        eprintln!("warning: override matches no transaction: {}", transaction_override.id());
+    }
+    let transfers = match_transfers(&mut sources, input.transfer_day_window);
+    Ok((sources, transfers))
+}
+
+/// Reads the transactions of every source, from the input directory or, if there is one, the
+/// ledger, with transactions repeated across overlapping files removed.
+pub fn read_transactions(input: &InputOptions) -> Result<SourceTransactions> {
+    read_transactions_and_duplicates(input).map(|(sources, _duplicates)| sources)
+}
+
+/// Like `read_transactions`, but also returns the duplicates it removed.
+pub fn read_transactions_and_duplicates(
+    input: &InputOptions,
+) -> Result<(SourceTransactions, Vec<Duplicate>)> {
+    let source_files = read_source_files(&input.input_dir)?;
+    if let Some(ledger_path) = &input.ledger_path {
+        let merge = read_merged(ledger_path, source_files)?;
+        return Ok((merge.sources, merge.duplicates));
+    }
+    let mut sources = vec![];
+    let mut duplicates = vec![];
+    for (source, files) in source_files {
+        let (transactions, source_duplicates) = deduplicate(&source, files);
+        sources.push((source, transactions));
+        duplicates.extend(source_duplicates);
+    }
+    Ok((sources, duplicates))
+}
+
+/// Reads and converts every CSV record or OFX transaction in `dir` into a transaction, grouped by
+/// source name and file. Reports all problems found rather than stopping at the first.
+pub fn read_source_files(dir: &Path) -> Result<Vec<SourceFiles>> {
+    let mut errors = vec![];
+    let mut sources = vec![];
+    for (source, csv_config, paths) in read_input(dir, &mut errors) {
+        let mut files = vec![];
+        for path in paths {
+            let transactions = match csv_config.format {
+                InputFormat::Csv => read_csv_transactions(&source, &csv_config, &path, &mut errors),
+                InputFormat::Ofx => read_ofx_transactions(&source, &csv_config, &path, &mut errors),
+                InputFormat::Qif => read_qif_transactions(&source, &csv_config, &path, &mut errors),
+            };
+            if let Some(transactions) = transactions {
+                files.push((path, transactions));
+            }
+        }
+        sources.push((source, files));
+    }
+    Error::from_all(errors)?;
+    Ok(sources)
+}
+
+fn read_csv_transactions(
+    source: &str,
+    csv_config: &CsvConfig,
+    path: &Path,
+    errors: &mut Vec<Error>,
+) -> Option<Vec<Transaction>> {
+    let csv_error = |error| Error::Csv {
+        source_name: source.to_string(),
+        path: path.to_path_buf(),
+        source: error,
+    };
+    let layout_error = |error| Error::Layout {
+        source_name: source.to_string(),
+        path: path.to_path_buf(),
+        source: error,
+    };
+    let contents = read_decoded(source, csv_config, path, errors)?;
+    let (contents, skipped_lines) = csv_config
+        .trim_lines(&contents)
+        .map_err(|error| errors.push(layout_error(error)))
+        .ok()?;
+    let mut reader = csv_config.reader(contents).map_err(|error| errors.push(layout_error(error))).ok()?;
+    let header = reader.headers().map_err(|error| errors.push(csv_error(error))).ok()?.clone();
+    let csv_config = csv_config
+        .with_header(&header)
+        .map_err(|error| errors.push(layout_error(error)))
+        .ok()?;
+
+    let record_error = |line: u64, error| Error::Record {
+        source_name: source.to_string(),
+        path: path.to_path_buf(),
+        line: skipped_lines + line,
+        source: error,
+    };
 
-pub fn read_input(
-    dir: PathBuf,
-) -> impl Iterator<Item=(String, CsvConfig, impl Iterator<Item=StringRecord>)> {
-    unwrap_or_exit(fs::read_dir(&dir), &dir)
-        .map(move |result| unwrap_or_exit(result, &dir))
-        .filter(|entry| {
-            let file_type = unwrap_or_exit(entry.file_type(), &entry.path());
-            file_type.is_dir()
+    let mut transactions = vec![];
+    for result in reader.records() {
+        let csv_record = match result {
+            Ok(csv_record) => csv_record,
+            Err(error) => {
+                // The csv crate counts lines from the header row, not from the top of the file.
+                errors.push(match error.kind() {
+                    csv::ErrorKind::UnequalLengths { pos: Some(position), expected_len, len } => record_error(
+                        position.line(),
+                        RecordError::FieldCount { expected: *expected_len, found: *len },
+                    ),
+                    _ => csv_error(error),
+                });
+                continue;
+            }
+        };
+        match csv_record_to_transaction(&csv_record, &csv_config) {
+            Ok(transaction) => transactions.push(transaction),
+            Err(error) => {
+                errors.push(record_error(csv_record.position().map_or(0, |position| position.line()), error))
+            }
+        }
+    }
+    Some(transactions)
+}
+
+fn read_ofx_transactions(
+    source: &str,
+    csv_config: &CsvConfig,
+    path: &Path,
+    errors: &mut Vec<Error>,
+) -> Option<Vec<Transaction>> {
+    let contents = read_decoded(source, csv_config, path, errors)?;
+    parse_ofx(&contents)
+        .map_err(|error| {
+            errors.push(Error::Ofx {
+                source_name: source.to_string(),
+                path: path.to_path_buf(),
+                source: error,
+            })
         })
-        .map(input_subdir_to_tuple)
+        .ok()
 }
 
-fn input_subdir_to_tuple(
-    subdir: DirEntry,
-) -> (String, CsvConfig, impl Iterator<Item=StringRecord>) {
-    let csv_config = input_subdir_to_csv_config(&subdir);
-    (
-        input_subdir_to_source_name(&subdir, &csv_config),
-        csv_config,
-        csv_file_path_iter_to_csv_record_iter(input_subdir_to_csv_file_path_iter(&subdir)),
-    )
+fn read_qif_transactions(
+    source: &str,
+    csv_config: &CsvConfig,
+    path: &Path,
+    errors: &mut Vec<Error>,
+) -> Option<Vec<Transaction>> {
+    let contents = read_decoded(source, csv_config, path, errors)?;
+    parse_qif(&contents, csv_config.date_format())
+        .map_err(|error| {
+            errors.push(Error::Qif {
+                source_name: source.to_string(),
+                path: path.to_path_buf(),
+                source: error,
+            })
+        })
+        .ok()
 }
 
-fn input_subdir_to_csv_config(subdir: &DirEntry) -> CsvConfig {
+/// Reads a file of a source and decodes it from the source's encoding.
+fn read_decoded(source: &str, csv_config: &CsvConfig, path: &Path, errors: &mut Vec<Error>) -> Option<String> {
+    let bytes = fs::read(path)
+        .map_err(|error| errors.push(Error::Io { path: path.to_path_buf(), source: error }))
+        .ok()?;
+    csv_config
+        .decode(&bytes)
+        .map_err(|error| {
+            errors.push(Error::Layout {
+                source_name: source.to_string(),
+                path: path.to_path_buf(),
+                source: error,
+            })
+        })
+        .ok()
+}
+
+/// Reads every source subdirectory of `dir`, skipping and adding to `errors` any unreadable
+/// directories and configs rather than stopping at the first.
+pub fn read_input(dir: &Path, errors: &mut Vec<Error>) -> Vec<SourceInput> {
+    let mut sources = vec![];
+    for subdir in read_subdirs(dir, errors) {
+        if let Some(source) = input_subdir_to_tuple(&subdir, errors) {
+            sources.push(source);
+        }
+    }
+    sources
+}
+
+fn read_subdirs(dir: &Path, errors: &mut Vec<Error>) -> Vec<DirEntry> {
+    let mut subdirs = vec![];
+    for entry in read_dir_entries(dir, errors) {
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => subdirs.push(entry),
+            Ok(_) => {}
+            Err(source) => errors.push(Error::Io { path: entry.path(), source }),
+        }
+    }
+    subdirs
+}
+
+fn read_dir_entries(dir: &Path, errors: &mut Vec<Error>) -> Vec<DirEntry> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(source) => {
+            errors.push(Error::Io { path: dir.to_path_buf(), source });
+            return vec![];
+        }
+    };
+    entries
+        .filter_map(|result| {
+            result
+                .map_err(|source| errors.push(Error::Io { path: dir.to_path_buf(), source }))
+                .ok()
+        })
+        .collect()
+}
+
+fn input_subdir_to_tuple(subdir: &DirEntry, errors: &mut Vec<Error>) -> Option<SourceInput> {
+    let csv_config = input_subdir_to_csv_config(subdir)
+        .map_err(|error| errors.push(error))
+        .ok()?;
+    let source_name = input_subdir_to_source_name(subdir, &csv_config)
+        .map_err(|error| errors.push(error))
+        .ok()?;
+    if csv_config.format == InputFormat::Csv {
+        csv_config
+            .check()
+            .map_err(|error| {
+                errors.push(Error::Layout {
+                    source_name: source_name.clone(),
+                    path: subdir.path().join("config.json"),
+                    source: error,
+                })
+            })
+            .ok()?;
+    }
+    let paths = input_subdir_to_file_paths(subdir, csv_config.format, errors);
+    Some((source_name, csv_config, paths))
+}
+
+fn input_subdir_to_csv_config(subdir: &DirEntry) -> Result<CsvConfig> {
     let mut config_path = subdir.path();
     config_path.push("config.json");
-    let contents = unwrap_or_exit(fs::read_to_string(&config_path), &config_path);
-    unwrap_or_exit(serde_json::from_str(&contents), &config_path)
+    let contents = read_to_string(&config_path)?;
+    serde_json::from_str(&contents).map_err(|source| Error::Config { path: config_path, source })
 }
 
-fn input_subdir_to_source_name(subdir: &DirEntry, csv_config: &CsvConfig) -> String {
+fn input_subdir_to_source_name(subdir: &DirEntry, csv_config: &CsvConfig) -> Result<String> {
     if !csv_config.source_alias.is_empty() {
-        csv_config.source_alias.clone()
+        Ok(csv_config.source_alias.clone())
     } else {
-        unwrap_or_exit_debug(subdir.file_name().into_string(), &subdir.path())
+        subdir
+            .file_name()
+            .into_string()
+            .map_err(|_| Error::FileName { path: subdir.path() })
     }
 }
 
-fn input_subdir_to_csv_file_path_iter(subdir: &DirEntry) -> impl Iterator<Item=PathBuf> {
-    let subdir_path = subdir.path();
-    unwrap_or_exit(fs::read_dir(&subdir_path), &subdir_path).filter_map(move |result| {
-        let entry = unwrap_or_exit(result, &subdir_path);
-        let file_name = unwrap_or_exit_debug(entry.file_name().into_string(), &entry.path());
-        if file_name.to_lowercase().ends_with(".csv") {
-            Some(entry.path())
-        } else {
-            None
+fn input_subdir_to_file_paths(
+    subdir: &DirEntry,
+    format: InputFormat,
+    errors: &mut Vec<Error>,
+) -> Vec<PathBuf> {
+    let has_format_extension = |file_name: &str| {
+        let extension = Path::new(file_name).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        extension.is_some_and(|extension| format.extensions().contains(&extension.as_str()))
+    };
+    let mut paths = vec![];
+    for entry in read_dir_entries(&subdir.path(), errors) {
+        match entry.file_name().into_string() {
+            Ok(file_name) if has_format_extension(&file_name) => paths.push(entry.path()),
+            Ok(_) => {}
+            Err(_) => errors.push(Error::FileName { path: entry.path() }),
         }
+    }
+    paths.sort();
+    paths
+}
+
+pub fn read_classification_rules(rules_path: &Path) -> Result<Vec<TransactionClassificationRule>> {
+    let contents = read_to_string(rules_path)?;
+    serde_json::from_str(&contents).map_err(|source| Error::Rules {
+        path: rules_path.to_path_buf(),
+        source,
     })
 }
 
-fn csv_file_path_iter_to_csv_record_iter<I>(paths: I) -> impl Iterator<Item=StringRecord>
-where
-    I: Iterator<Item=PathBuf>,
-{
-    paths
-        .map(|path| {
-            unwrap_or_exit(csv::Reader::from_path(&path), &path)
-                .into_records()
-                .map(move |result| unwrap_or_exit(result, &path))
-        })
-        .flatten()
+pub fn read_overrides(overrides_path: &Path) -> Result<Vec<TransactionOverride>> {
+    let contents = read_to_string(overrides_path)?;
+    serde_json::from_str(&contents).map_err(|source| Error::Overrides {
+        path: overrides_path.to_path_buf(),
+        source,
+    })
 }
 
-pub fn read_classification_rules(dir: PathBuf) -> Vec<TransactionClassificationRule> {
-    let mut rules_path = dir;
-    rules_path.push("rules.json");
-    let contents = unwrap_or_exit(fs::read_to_string(&rules_path), &rules_path);
-    unwrap_or_exit(serde_json::from_str(&contents), &rules_path)
+pub fn read_budgets(budgets_path: &Path) -> Result<Vec<Budget>> {
+    let contents = read_to_string(budgets_path)?;
+    serde_json::from_str(&contents).map_err(|source| Error::Budgets {
+        path: budgets_path.to_path_buf(),
+        source,
+    })
 }
 
-fn unwrap_or_exit<T, E: Display>(result: Result<T, E>, path: &Path) -> T {
-    match result {
-        Ok(value) => value,
-        Err(error) => {
-            println!("{}: {}", path.display(), error);
-            exit(1)
-        }
-    }
+pub fn read_exclusion_policy(policy_path: &Path) -> Result<ExclusionPolicy> {
+    let contents = read_to_string(policy_path)?;
+    serde_json::from_str(&contents).map_err(|source| Error::Exclusions {
+        path: policy_path.to_path_buf(),
+        source,
+    })
 }
 
-fn unwrap_or_exit_debug<T, E: Debug>(result: Result<T, E>, path: &Path) -> T {
-    match result {
-        Ok(value) => value,
-        Err(error) => {
-            println!("{}: {:?}", path.display(), error);
-            exit(1)
-        }
-    }
+fn read_to_string(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })
 }
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use chrono::Datelike;
+
+use crate::category_tree::category_path;
+use crate::error::Result;
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::file_io::{read_classified_transactions, InputOptions, SourceTransactions};
+use crate::icicle_chart_data::{icicle_chart_data, IcicleOptions};
+use crate::money::Money;
+
+const TEMPLATE: &str = include_str!("html_report.html");
+
+/// Writes a self-contained HTML page with the zoomable icicle chart and tables of category and
+/// monthly totals, which can be opened straight from disk.
+pub fn generate_html_report(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    options: IcicleOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let sources = read_classified_transactions(input)?;
+    write!(out, "{}", html_report(&sources, policy, options))?;
+    Ok(())
+}
+
+pub fn html_report(sources: &SourceTransactions, policy: &ExclusionPolicy, options: IcicleOptions) -> String {
+    let chart_data = serde_json::to_string(&icicle_chart_data(sources, policy, options)).unwrap();
+    TEMPLATE
+        .replace("{{TITLE}}", "Expenses")
+        .replace("{{CATEGORY_ROWS}}", &category_rows(sources, policy))
+        .replace("{{MONTH_ROWS}}", &month_rows(sources, policy))
+        // Keeps a "</script>" inside a name from ending the script early.
+        .replace("{{CHART_DATA}}", &chart_data.replace("</", "<\\/"))
+}
+
+/// One row per category and parent category, in tree order, with subcategories indented.
+fn category_rows(sources: &SourceTransactions, policy: &ExclusionPolicy) -> String {
+    let mut totals: BTreeMap<Vec<String>, Money> = BTreeMap::new();
+    for (_source, transactions) in sources {
+        for transaction in transactions.iter().filter(|transaction| !policy.excludes(transaction)) {
+            let path = category_path(&transaction.category);
+            for depth in 1..=path.len() {
+                let ancestor = path[..depth].iter().map(|level| level.to_string()).collect();
+                *totals.entry(ancestor).or_default() += transaction.amount;
+            }
+        }
+    }
+
+    let mut rows = String::new();
+    for (path, total) in totals {
+        rows.push_str(&format!(
+            "<tr><td style=\"padding-left: {}em\">{}</td><td class=\"amount\">{}</td></tr>\n",
+            path.len(),
+            escape_html(path.last().unwrap()),
+            total
+        ));
+    }
+    rows
+}
+
+fn month_rows(sources: &SourceTransactions, policy: &ExclusionPolicy) -> String {
+    let mut totals: BTreeMap<(i32, u32), (Money, Money)> = BTreeMap::new();
+    for (_source, transactions) in sources {
+        for transaction in transactions.iter().filter(|transaction| !policy.excludes(transaction)) {
+            let (income, spending) = totals
+                .entry((transaction.date.year(), transaction.date.month()))
+                .or_default();
+            if transaction.amount.is_positive() {
+                *income += transaction.amount;
+            } else {
+                *spending -= transaction.amount;
+            }
+        }
+    }
+
+    let mut rows = String::new();
+    for ((year, month), (income, spending)) in totals {
+        rows.push_str(&format!(
+            "<tr><td>{}-{:02}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td></tr>\n",
+            year,
+            month,
+            income,
+            spending,
+            income - spending
+        ));
+    }
+    rows
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::transactions::{test_transaction, Transaction};
+
+    use super::*;
+
+    fn transaction(category: &str, cents: i64) -> Transaction {
+        Transaction { category: category.to_string(), ..test_transaction(NaiveDate::from_ymd(2023, 7, 1), "", cents) }
+    }
+
+    #[test]
+    fn totals_parent_categories_in_tree_order() {
+        let sources = vec![("Card".to_string(), vec![
+            transaction("Home/Utilities/Electric", -5000),
+            transaction("Home Office", -700),
+            transaction("Home/Rent", -100000),
+            transaction("<Misc>", -1),
+        ])];
+
+        let rows: Vec<String> = category_rows(&sources, &ExclusionPolicy::default())
+            .lines()
+            .map(|row| row.replace("<tr><td style=\"padding-left: ", "").replace("</td></tr>", ""))
+            .collect();
+
+        assert_eq!(rows, vec![
+            "1em\">&lt;Misc&gt;</td><td class=\"amount\">-0.01",
+            "1em\">Home</td><td class=\"amount\">-1050.00",
+            "2em\">Rent</td><td class=\"amount\">-1000.00",
+            "2em\">Utilities</td><td class=\"amount\">-50.00",
+            "3em\">Electric</td><td class=\"amount\">-50.00",
+            "1em\">Home Office</td><td class=\"amount\">-7.00",
+        ]);
+    }
+}
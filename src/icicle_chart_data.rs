@@ -1,126 +1,350 @@
 #![allow(dead_code)]
 
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::Write;
 
-use chrono::NaiveDate;
+use clap::ValueEnum;
 use serde::Serialize;
 
-use crate::file_io::{read_classification_rules, read_input};
-use crate::transaction_classification::TransactionClassifier;
-use crate::transactions::{csv_record_to_transaction, Transaction};
+use crate::category_tree::CategoryTree;
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::error::Result;
+use crate::file_io::{read_classified_transactions, InputOptions, SourceTransactions};
+use crate::money::{Money, Rounding};
+use crate::transactions::Transaction;
 
+/// A node of the zoomable icicle chart: a category or description grouping the nodes beneath
+/// it, or a single transaction.
 #[derive(Debug, Serialize)]
-pub struct TransactionDataNode {
-    name: String,
-    value: u32,
+#[serde(untagged)]
+pub enum DataNode {
+    Group { name: String, children: Vec<DataNode> },
+    Transaction { name: String, value: Money },
 }
 
-#[derive(Debug, Serialize)]
-pub struct DescriptionDataNode {
-    name: String,
-    children: Vec<TransactionDataNode>,
+/// Which side of the cash flow the chart shows. Net shows an "Income" and a "Spending" node side
+/// by side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IcicleMode {
+    Spending,
+    Income,
+    Net,
 }
 
-#[derive(Debug, Serialize)]
-pub struct CategoryDataNode {
-    name: String,
-    children: Vec<DescriptionDataNode>,
+/// How to order the children of each node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IcicleSort {
+    /// Largest amount first, then by name
+    Amount,
+    /// By name, then largest amount first
+    Name,
 }
 
-#[derive(Debug, Serialize)]
-pub struct RootDataNode {
-    name: String,
-    children: Vec<CategoryDataNode>,
+#[derive(Clone, Copy, Debug)]
+pub struct IcicleOptions {
+    pub mode: IcicleMode,
+    pub rounding: Rounding,
+    pub sort: IcicleSort,
+    pub pretty: bool,
+}
+
+impl DataNode {
+    fn name(&self) -> &str {
+        match self {
+            DataNode::Group { name, .. } | DataNode::Transaction { name, .. } => name,
+        }
+    }
+
+    fn value(&self) -> Money {
+        match self {
+            DataNode::Group { children, .. } => children.iter().map(DataNode::value).sum(),
+            DataNode::Transaction { value, .. } => *value,
+        }
+    }
+
+    fn sort(&mut self, sort: IcicleSort) {
+        if let DataNode::Group { children, .. } = self {
+            for child in children.iter_mut() {
+                child.sort(sort);
+            }
+            match sort {
+                IcicleSort::Amount => children.sort_by(|a, b| {
+                    b.value().cmp(&a.value()).then_with(|| a.name().cmp(b.name()))
+                }),
+                IcicleSort::Name => children.sort_by(|a, b| {
+                    a.name().cmp(b.name()).then_with(|| b.value().cmp(&a.value()))
+                }),
+            }
+        }
+    }
 }
 
-type TransactionDataNodes = Vec<TransactionDataNode>;
-type DescriptionMap = HashMap<String, TransactionDataNodes>;
-type CategoryMap = HashMap<String, DescriptionMap>;
+type SourceTransaction = (String, Transaction);
 
-pub fn generate_icicle_chart_data() {
-    let category_map = gather_category_map();
-    let category_data_nodes = categories_to_data_nodes(category_map);
-    let root_data_node = RootDataNode {
+/// Writes the chart data with one level per category level, then descriptions, then
+/// transactions. Within each category, refunds are netted against spending and vice versa, so a
+/// category appears on the side of its net amount.
+pub fn generate_icicle_chart_data(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    options: IcicleOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let sources = read_classified_transactions(input)?;
+    let root_data_node = icicle_chart_data(&sources, policy, options);
+    let json = if options.pretty {
+        serde_json::to_string_pretty(&root_data_node)
+    } else {
+        serde_json::to_string(&root_data_node)
+    };
+    writeln!(out, "{}", json.unwrap())?;
+    Ok(())
+}
+
+/// Builds the chart's root node from classified transactions, with children sorted as `options`
+/// asks.
+pub fn icicle_chart_data(
+    sources: &SourceTransactions,
+    policy: &ExclusionPolicy,
+    options: IcicleOptions,
+) -> DataNode {
+    let rounding = options.rounding;
+    let category_tree = gather_category_tree(sources, policy);
+    let (spending_tree, income_tree) = split_by_side(category_tree);
+    let spending = DataNode::Group {
         name: "Spending".to_string(),
-        children: category_data_nodes,
+        children: category_tree_to_data_nodes(spending_tree, rounding),
+    };
+    let income = DataNode::Group {
+        name: "Income".to_string(),
+        children: category_tree_to_data_nodes(income_tree, rounding),
     };
-    println!("{}", serde_json::to_string(&root_data_node).unwrap());
+    let mut root_data_node = match options.mode {
+        IcicleMode::Spending => spending,
+        IcicleMode::Income => income,
+        IcicleMode::Net => DataNode::Group {
+            name: "Net".to_string(),
+            children: vec![income, spending],
+        },
+    };
+    root_data_node.sort(options.sort);
+    root_data_node
 }
 
-fn gather_category_map() -> CategoryMap {
-    let rules = read_classification_rules(PathBuf::from("input"));
-    let classifier = TransactionClassifier::new(rules);
-
-    let mut categories: CategoryMap = HashMap::new();
-
-    for (source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
-        for csv_record in cvs_records {
-            let transaction = classifier
-                .classify_transaction(csv_record_to_transaction(&csv_record, &csv_config));
-            if !should_exclude_transaction(&transaction) {
-                let descriptions = categories
-                    .entry(transaction.category.clone())
-                    .or_insert(HashMap::new());
-                let transactions = descriptions
-                    .entry(transaction.description.clone())
-                    .or_insert(vec![]);
-                if transaction.amount <= 0.0 {
-                    transactions.push(transaction_to_data_node(&source, transaction));
-                }
+fn gather_category_tree(
+    sources: &SourceTransactions,
+    policy: &ExclusionPolicy,
+) -> CategoryTree<SourceTransaction> {
+    let mut category_tree = CategoryTree::default();
+
+    for (source, transactions) in sources {
+        for transaction in transactions {
+            if !policy.excludes(transaction) {
+                category_tree.insert(&transaction.category, (source.clone(), transaction.clone()));
             }
         }
     }
 
-    categories
+    category_tree
 }
 
-fn transaction_to_data_node(source: &str, transaction: Transaction) -> TransactionDataNode {
-    TransactionDataNode {
+/// Nets the transactions of each category and splits the tree into its spending and income
+/// sides.
+fn split_by_side(
+    category_tree: CategoryTree<SourceTransaction>,
+) -> (CategoryTree<SourceTransaction>, CategoryTree<SourceTransaction>) {
+    let mut spending = CategoryTree::default();
+    let mut income = CategoryTree::default();
+    for item in net_transactions(category_tree.items) {
+        if item.1.amount.is_negative() {
+            spending.items.push(item);
+        } else {
+            income.items.push(item);
+        }
+    }
+    for (category, subtree) in category_tree.subcategories {
+        let (spending_subtree, income_subtree) = split_by_side(subtree);
+        spending.subcategories.insert(category.clone(), spending_subtree);
+        income.subcategories.insert(category, income_subtree);
+    }
+    (spending, income)
+}
+
+/// Offsets the transactions of one category against each other so that only those on the side
+/// of the category's total remain, reduced by the ones on the other side. Each offsetting
+/// transaction, such as a refund, reduces transactions with the same description first, then
+/// any others, largest first.
+fn net_transactions(items: Vec<SourceTransaction>) -> Vec<SourceTransaction> {
+    let total: Money = items.iter().map(|(_source, transaction)| transaction.amount).sum();
+    let (mut kept, offsets): (Vec<SourceTransaction>, Vec<SourceTransaction>) = items
+        .into_iter()
+        .filter(|(_source, transaction)| transaction.amount != Money::ZERO)
+        .partition(|(_source, transaction)| transaction.amount.is_negative() == total.is_negative());
+    kept.sort_by_key(|(_source, transaction)| std::cmp::Reverse(transaction.amount.abs()));
+
+    let mut pool = Money::ZERO;
+    for (_source, offset) in &offsets {
+        let remaining = offset_matching(&mut kept, offset.amount.abs(), |transaction| {
+            transaction.description == offset.description
+        });
+        pool += remaining;
+    }
+    offset_matching(&mut kept, pool, |_transaction| true);
+
+    kept.retain(|(_source, transaction)| transaction.amount != Money::ZERO);
+    kept
+}
+
+/// Moves the amounts of matching transactions toward zero by up to `offset` in total, returning
+/// what is left of it.
+fn offset_matching<F>(kept: &mut [SourceTransaction], mut offset: Money, matches: F) -> Money
+where
+    F: Fn(&Transaction) -> bool,
+{
+    for (_source, transaction) in kept.iter_mut() {
+        if offset == Money::ZERO {
+            break;
+        }
+        if !matches(transaction) {
+            continue;
+        }
+        let reduction = offset.min(transaction.amount.abs());
+        offset -= reduction;
+        if transaction.amount.is_negative() {
+            transaction.amount += reduction;
+        } else {
+            transaction.amount -= reduction;
+        }
+    }
+    offset
+}
+
+fn transaction_to_data_node(
+    source: &str,
+    transaction: Transaction,
+    rounding: Rounding,
+) -> DataNode {
+    DataNode::Transaction {
         name: format!("{}, {},", transaction.date, source),
-        value: -transaction.amount.round() as u32,
+        value: transaction.amount.abs().round(rounding),
     }
 }
 
-fn categories_to_data_nodes(category_map: CategoryMap) -> Vec<CategoryDataNode> {
-    let mut category_data_nodes = vec![];
-    for (category, description_map) in category_map {
-        let mut category_data_node = CategoryDataNode {
-            name: category,
-            children: vec![],
-        };
-        for (description, transaction_data_nodes) in description_map {
-            category_data_node.children.push(DescriptionDataNode {
-                name: description,
-                children: transaction_data_nodes,
-            });
+/// Converts one side of the category tree, leaving out categories with nothing on that side.
+fn category_tree_to_data_nodes(
+    category_tree: CategoryTree<SourceTransaction>,
+    rounding: Rounding,
+) -> Vec<DataNode> {
+    let mut data_nodes = vec![];
+    for (category, subtree) in category_tree.subcategories {
+        let children = category_tree_to_data_nodes(subtree, rounding);
+        if !children.is_empty() {
+            data_nodes.push(DataNode::Group { name: category, children });
         }
-        category_data_nodes.push(category_data_node);
     }
-    category_data_nodes
+
+    let mut descriptions: HashMap<String, Vec<DataNode>> = HashMap::new();
+    for (source, transaction) in category_tree.items {
+        descriptions
+            .entry(transaction.description.clone())
+            .or_default()
+            .push(transaction_to_data_node(&source, transaction, rounding));
+    }
+    for (description, transaction_data_nodes) in descriptions {
+        data_nodes.push(DataNode::Group {
+            name: description,
+            children: transaction_data_nodes,
+        });
+    }
+    data_nodes
 }
 
-pub fn should_exclude_transaction(transaction: &Transaction) -> bool {
-    let exclude_categories: HashSet<&'static str> =
-        ["Dividend", "Investment", "Reimbursed", "Salary", "Tax", "Transfer"]
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate};
+
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn item(day: u32, description: &str, cents: i64) -> SourceTransaction {
+        ("Card".to_string(), Transaction {
+            description: description.to_string(),
+            category: "Shopping".to_string(),
+            ..test_transaction(NaiveDate::from_ymd(2023, 5, day), &description.to_uppercase(), cents)
+        })
+    }
+
+    fn amounts(items: &[SourceTransaction]) -> Vec<(u32, Money)> {
+        items
             .iter()
-            .cloned()
-            .collect();
-    let exclude_positive_categories: HashSet<&'static str> =
-        ["Travel", "Unknown"].iter().cloned().collect();
+            .map(|(_source, transaction)| (transaction.date.day(), transaction.amount))
+            .collect()
+    }
 
-    if transaction.date < NaiveDate::from_ymd(2023, 1, 1) {
-        return true;
+    fn day_amounts(day_cents: &[(u32, i64)]) -> Vec<(u32, Money)> {
+        day_cents.iter().map(|&(day, cents)| (day, Money::from_cents(cents))).collect()
     }
-    if transaction.date >= NaiveDate::from_ymd(2024, 1, 1) {
-        return true;
+
+    #[test]
+    fn nets_refunds_against_same_description_first() {
+        let netted = net_transactions(vec![
+            item(1, "Amazon", -5000),
+            item(2, "Target", -3000),
+            item(3, "Target", -1000),
+            item(4, "Target", 3500),
+        ]);
+
+        assert_eq!(amounts(&netted), day_amounts(&[(1, -5000), (3, -500)]));
     }
-    if exclude_categories.contains(&*transaction.category) {
-        return true;
+
+    #[test]
+    fn spills_unmatched_refunds_onto_largest_transactions() {
+        let netted = net_transactions(vec![
+            item(1, "Amazon", -5000),
+            item(2, "Target", -3000),
+            item(3, "Costco", 6000),
+        ]);
+
+        assert_eq!(amounts(&netted), day_amounts(&[(2, -2000)]));
     }
-    if transaction.amount > 0.0 && exclude_positive_categories.contains(&*transaction.category) {
-        return true;
+
+    #[test]
+    fn sorts_children_by_amount_or_name() {
+        let leaf = |name: &str, cents| DataNode::Transaction {
+            name: name.to_string(),
+            value: Money::from_cents(cents),
+        };
+        let group = |children| DataNode::Group { name: "Root".to_string(), children };
+        let names = |node: &DataNode| match node {
+            DataNode::Group { children, .. } => children.iter().map(|child| child.name().to_string()).collect(),
+            DataNode::Transaction { .. } => vec![],
+        };
+
+        let mut by_amount = group(vec![leaf("b", 100), leaf("c", 300), leaf("a", 100)]);
+        by_amount.sort(IcicleSort::Amount);
+        let mut by_name = group(vec![leaf("b", 100), leaf("c", 300), leaf("a", 100)]);
+        by_name.sort(IcicleSort::Name);
+
+        assert_eq!(names(&by_amount), vec!["c", "a", "b"]);
+        assert_eq!(names(&by_name), vec!["a", "b", "c"]);
+        assert_eq!(by_amount.value(), Money::from_cents(500));
     }
 
-    false
+    #[test]
+    fn puts_categories_on_the_side_of_their_net_amount() {
+        let mut category_tree = CategoryTree::default();
+        category_tree.insert("Shopping", item(1, "Amazon", -5000));
+        category_tree.insert("Shopping", item(2, "Amazon", 1000));
+        category_tree.insert("Reimbursed", item(3, "Employer", 8000));
+        category_tree.insert("Reimbursed", item(4, "Hotel", -6000));
+
+        let (spending, income) = split_by_side(category_tree);
+
+        assert_eq!(amounts(&spending.subcategories["Shopping"].items), day_amounts(&[(1, -4000)]));
+        assert!(spending.subcategories["Reimbursed"].items.is_empty());
+        assert!(income.subcategories["Shopping"].items.is_empty());
+        assert_eq!(amounts(&income.subcategories["Reimbursed"].items), day_amounts(&[(3, 2000)]));
+        assert_eq!(category_tree_to_data_nodes(income, Rounding::Cents).len(), 1);
+    }
 }
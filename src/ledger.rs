@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::deduplication::{deduplicate, Duplicate};
+use crate::error::{Error, Result};
+use crate::exclusion_policy::deserialize_date;
+use crate::file_io::{read_source_files, InputOptions, SourceFiles, SourceTransactions};
+use crate::money::Money;
+use crate::transactions::Transaction;
+
+/// One line of the ledger, an append-only JSON Lines file. Each imported file is recorded after
+/// its transactions, so an interrupted import is simply redone, with the transactions it already
+/// wrote dropped as duplicates.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LedgerEntry {
+    Import(ImportedFile),
+    Transaction(LedgerTransaction),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ImportedFile {
+    source: String,
+    file: String,
+    hash: String,
+    transactions: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LedgerTransaction {
+    source: String,
+    #[serde(serialize_with = "serialize_date", deserialize_with = "deserialize_date")]
+    date: NaiveDate,
+    raw_description: String,
+    raw_category: String,
+    amount: Money,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    bank_id: String,
+}
+
+impl LedgerTransaction {
+    fn new(source: &str, transaction: &Transaction) -> Self {
+        LedgerTransaction {
+            source: source.to_string(),
+            date: transaction.date,
+            raw_description: transaction.raw_description.clone(),
+            raw_category: transaction.raw_category.clone(),
+            amount: transaction.amount,
+            bank_id: transaction.bank_id.clone(),
+        }
+    }
+
+    fn into_transaction(self) -> Transaction {
+        Transaction {
+            date: self.date,
+            raw_description: self.raw_description,
+            raw_category: self.raw_category,
+            amount: self.amount,
+            description: "".to_string(),
+            category: "".to_string(),
+            notes: "".to_string(),
+            excluded: false,
+            bank_id: self.bank_id,
+        }
+    }
+}
+
+fn serialize_date<S: Serializer>(date: &NaiveDate, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(date)
+}
+
+/// Like `SourceFiles`, with each file's content hash.
+type HashedSourceFiles = (String, Vec<(PathBuf, String, Vec<Transaction>)>);
+
+#[derive(Debug, PartialEq)]
+pub enum ImportStatus {
+    /// A file with the same contents was imported into the same source before.
+    AlreadyImported,
+    Imported { transactions: usize, duplicates: usize },
+}
+
+#[derive(Debug)]
+pub struct FileImport {
+    pub source: String,
+    pub path: PathBuf,
+    pub status: ImportStatus,
+}
+
+/// The ledger's transactions combined with those of input files not imported yet.
+#[derive(Debug)]
+pub struct Merge {
+    pub sources: SourceTransactions,
+    pub duplicates: Vec<Duplicate>,
+    pub imports: Vec<FileImport>,
+    entries: Vec<LedgerEntry>,
+}
+
+/// Reads the ledger and merges in the input files it has not seen, without changing the ledger.
+/// Transactions a new file repeats from the ledger or from files merged before it are dropped
+/// the same way as between overlapping CSV files. A missing ledger is empty.
+pub fn read_merged(ledger_path: &Path, input: Vec<SourceFiles>) -> Result<Merge> {
+    let entries = read_entries(ledger_path)?;
+    let mut hashed_input = vec![];
+    for (source, files) in input {
+        let mut hashed_files = vec![];
+        for (path, transactions) in files {
+            let contents = fs::read(&path).map_err(|source| Error::Io { path: path.clone(), source })?;
+            hashed_files.push((path, content_hash(&contents), transactions));
+        }
+        hashed_input.push((source, hashed_files));
+    }
+    Ok(merge(ledger_path, entries, hashed_input))
+}
+
+/// Appends the transactions of new files in the input directory to the ledger and prints what
+/// was imported from each file. Imported files can be removed from the input directory
+/// afterwards, leaving only their source's config.json.
+pub fn import(input: &InputOptions, ledger_path: &Path, out: &mut dyn Write) -> Result<()> {
+    let merge = read_merged(ledger_path, read_source_files(&input.input_dir)?)?;
+    let mut added = 0;
+    for import in &merge.imports {
+        write!(out, "{}: {}: ", import.source, import.path.display())?;
+        match import.status {
+            ImportStatus::AlreadyImported => writeln!(out, "already imported")?,
+            ImportStatus::Imported { transactions, duplicates } => {
+                writeln!(out, "{} new, {} duplicates", transactions, duplicates)?;
+                added += transactions;
+            }
+        }
+    }
+    append_entries(ledger_path, &merge.entries)?;
+    writeln!(out, "Added {} transactions to {}", added, ledger_path.display())?;
+    Ok(())
+}
+
+fn merge(ledger_path: &Path, entries: Vec<LedgerEntry>, input: Vec<HashedSourceFiles>) -> Merge {
+    let mut sources: SourceTransactions = vec![];
+    let mut imported = HashSet::new();
+    for entry in entries {
+        match entry {
+            LedgerEntry::Import(file) => {
+                imported.insert((file.source, file.hash));
+            }
+            LedgerEntry::Transaction(transaction) => {
+                let source = transaction.source.clone();
+                source_transactions(&mut sources, &source).push(transaction.into_transaction());
+            }
+        }
+    }
+
+    let mut merge = Merge { sources: vec![], duplicates: vec![], imports: vec![], entries: vec![] };
+    for (source, files) in input {
+        for (path, hash, transactions) in files {
+            if !imported.insert((source.clone(), hash.clone())) {
+                merge.imports.push(FileImport { source: source.clone(), path, status: ImportStatus::AlreadyImported });
+                continue;
+            }
+            let current = source_transactions(&mut sources, &source);
+            let existing = current.len();
+            let (kept, duplicates) = deduplicate(&source, vec![
+                (ledger_path.to_path_buf(), mem::take(current)),
+                (path.clone(), transactions),
+            ]);
+            *current = kept;
+            for transaction in &current[existing..] {
+                merge.entries.push(LedgerEntry::Transaction(LedgerTransaction::new(&source, transaction)));
+            }
+            merge.entries.push(LedgerEntry::Import(ImportedFile {
+                source: source.clone(),
+                file: path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+                hash,
+                transactions: current.len() - existing,
+            }));
+            let status = ImportStatus::Imported { transactions: current.len() - existing, duplicates: duplicates.len() };
+            merge.imports.push(FileImport { source: source.clone(), path, status });
+            merge.duplicates.extend(duplicates);
+        }
+    }
+    merge.sources = sources;
+    merge
+}
+
+fn source_transactions<'a>(sources: &'a mut SourceTransactions, source: &str) -> &'a mut Vec<Transaction> {
+    let index = match sources.iter().position(|(name, _transactions)| name == source) {
+        Some(index) => index,
+        None => {
+            sources.push((source.to_string(), vec![]));
+            sources.len() - 1
+        }
+    };
+    &mut sources[index].1
+}
+
+fn read_entries(ledger_path: &Path) -> Result<Vec<LedgerEntry>> {
+    let io_error = |source| Error::Io { path: ledger_path.to_path_buf(), source };
+    let file = match fs::File::open(ledger_path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(io_error(error)),
+    };
+    let mut entries = vec![];
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line).map_err(|source| Error::Ledger {
+            path: ledger_path.to_path_buf(),
+            line: index as u64 + 1,
+            source,
+        })?);
+    }
+    Ok(entries)
+}
+
+fn append_entries(ledger_path: &Path, entries: &[LedgerEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let io_error = |source| Error::Io { path: ledger_path.to_path_buf(), source };
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry).unwrap());
+        lines.push('\n');
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(ledger_path).map_err(io_error)?;
+    file.write_all(lines.as_bytes()).map_err(io_error)
+}
+
+/// FNV-1a, which unlike the standard library's hasher is stable across builds.
+fn content_hash(contents: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in contents {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn transaction(day: u32, raw_description: &str, cents: i64) -> Transaction {
+        test_transaction(NaiveDate::from_ymd(2023, 1, day), raw_description, cents)
+    }
+
+    fn descriptions(transactions: &[Transaction]) -> Vec<&str> {
+        transactions.iter().map(|transaction| transaction.raw_description.as_str()).collect()
+    }
+
+    fn round_trip(entries: &[LedgerEntry]) -> Vec<LedgerEntry> {
+        entries
+            .iter()
+            .map(|entry| serde_json::from_str(&serde_json::to_string(entry).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn imports_only_new_files_and_transactions() {
+        let ledger = Path::new("ledger.jsonl");
+        let first = merge(ledger, vec![], vec![("Card".to_string(), vec![
+            (PathBuf::from("jan.csv"), "a".to_string(), vec![transaction(1, "ACME", -100), transaction(1, "ACME", -100)]),
+        ])]);
+        assert_eq!(first.imports[0].status, ImportStatus::Imported { transactions: 2, duplicates: 0 });
+
+        let second = merge(ledger, round_trip(&first.entries), vec![("Card".to_string(), vec![
+            (PathBuf::from("jan.csv"), "a".to_string(), vec![transaction(1, "ACME", -100), transaction(1, "ACME", -100)]),
+            (PathBuf::from("feb.csv"), "b".to_string(), vec![transaction(1, "ACME", -100), transaction(2, "SAFEWAY", -200)]),
+        ])]);
+
+        assert_eq!(second.imports[0].status, ImportStatus::AlreadyImported);
+        assert_eq!(second.imports[1].status, ImportStatus::Imported { transactions: 1, duplicates: 1 });
+        assert_eq!(descriptions(&second.sources[0].1), vec!["ACME", "ACME", "SAFEWAY"]);
+        assert_eq!(second.entries.len(), 2);
+    }
+
+    #[test]
+    fn reads_sources_from_ledger_alone() {
+        let first = merge(Path::new("ledger.jsonl"), vec![], vec![
+            ("Card".to_string(), vec![(PathBuf::from("jan.csv"), "a".to_string(), vec![transaction(3, "ACME", -100)])]),
+            ("Checking".to_string(), vec![(PathBuf::from("jan.csv"), "a".to_string(), vec![transaction(4, "RENT", -500)])]),
+        ]);
+
+        let merged = merge(Path::new("ledger.jsonl"), round_trip(&first.entries), vec![]);
+
+        assert_eq!(merged.sources.len(), 2);
+        assert_eq!(merged.sources[1].0, "Checking");
+        assert_eq!(merged.sources[1].1[0].date, NaiveDate::from_ymd(2023, 1, 4));
+        assert_eq!(merged.sources[1].1[0].amount, Money::from_cents(-500));
+    }
+}
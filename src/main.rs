@@ -1,16 +1,322 @@
+mod budgets;
+mod category_tree;
 mod classification_extras;
+mod deduplication;
+mod detect;
+mod encoding;
+mod error;
+mod exclusion_policy;
 mod file_io;
+mod html_report;
 mod icicle_chart_data;
+mod ledger;
+mod money;
+mod ofx;
+mod overrides;
+mod period_report;
+mod qif;
+mod server;
+mod splits;
 mod transaction_classification;
 mod transaction_extras;
 mod transactions;
+mod transfer_matching;
+mod watch;
+
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+
+use crate::error::{Error, Result};
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::file_io::{read_exclusion_policy, InputOptions};
+use crate::icicle_chart_data::{IcicleMode, IcicleOptions, IcicleSort};
+use crate::money::Rounding;
+use crate::period_report::{PeriodKind, ReportFormat};
+
+#[derive(Debug, Parser)]
+#[command(about = "Aggregate expenses")]
+struct Cli {
+    /// Directory containing one subdirectory of CSV files per source
+    #[arg(long, global = true, default_value = "input")]
+    input_dir: PathBuf,
+
+    /// Classification rules file [default: <INPUT_DIR>/rules.json]
+    #[arg(long, global = true)]
+    rules: Option<PathBuf>,
+
+    /// Per-transaction overrides file [default: <INPUT_DIR>/overrides.json if present]
+    #[arg(long, global = true)]
+    overrides: Option<PathBuf>,
+
+    /// Ledger of imported transactions to read alongside new input files
+    /// [default: <INPUT_DIR>/ledger.jsonl if present]
+    #[arg(long, global = true)]
+    ledger: Option<PathBuf>,
+
+    /// Exclusion policy file [default: <INPUT_DIR>/exclusions.json if present]
+    #[arg(long, global = true)]
+    exclusions: Option<PathBuf>,
+
+    /// Exclude transactions before this date (YYYY-MM-DD), overriding the exclusion policy
+    #[arg(long, global = true, value_parser = exclusion_policy::parse_date)]
+    from: Option<NaiveDate>,
+
+    /// Exclude transactions on or after this date (YYYY-MM-DD), overriding the exclusion policy
+    #[arg(long, global = true, value_parser = exclusion_policy::parse_date)]
+    to: Option<NaiveDate>,
+
+    /// Match transfers between sources whose dates are at most this many days apart
+    #[arg(long, global = true, default_value_t = 3)]
+    transfer_days: i64,
+
+    /// Write output to this file instead of stdout
+    #[arg(short, long, global = true)]
+    output: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print zoomable icicle chart data as JSON
+    Icicle {
+        /// Show spending, income, or both side by side with each category on its net side
+        #[arg(long, value_enum, default_value_t = IcicleMode::Spending)]
+        mode: IcicleMode,
+
+        /// Round each transaction's amount to this unit
+        #[arg(long, value_enum, default_value_t = Rounding::Dollars)]
+        round_to: Rounding,
+
+        /// Order the children of each node
+        #[arg(long, value_enum, default_value_t = IcicleSort::Amount)]
+        sort: IcicleSort,
+
+        /// Pretty-print the JSON
+        #[arg(long)]
+        pretty: bool,
+    },
+    /// Print a self-contained HTML report with the icicle chart and category and monthly totals
+    Report {
+        /// Show spending, income, or both side by side with each category on its net side
+        #[arg(long, value_enum, default_value_t = IcicleMode::Spending)]
+        mode: IcicleMode,
+    },
+    /// Serve the report, icicle chart data and transaction lists on localhost, re-reading the
+    /// input on every request
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// Reclassify whenever the input, rules or overrides change, printing what changed
+    Watch {
+        /// Seconds between checks for changes
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+    },
+    /// Print all transactions as CSV
+    ExportCsv,
+    /// Print all transactions, grouped by source
+    PrintTransactions,
+    /// Print the total for each category
+    SumCategories {
+        /// Roll subcategories up into their top DEPTH levels, e.g. 1 for "Home" only
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        depth: Option<u64>,
+    },
+    /// Print category totals per month, quarter, year or custom period, with year-over-year changes
+    Periods {
+        #[arg(long, value_enum, default_value_t = PeriodKind::Month)]
+        period: PeriodKind,
+
+        /// Comma-separated period boundaries (YYYY-MM-DD) for --period custom
+        #[arg(long, value_delimiter = ',', value_parser = exclusion_policy::parse_date)]
+        breaks: Vec<NaiveDate>,
+
+        /// Roll subcategories up into their top DEPTH levels
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        depth: Option<u64>,
+
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Compare spending against budgets, flagging overruns and projecting the year-end total
+    Budget {
+        /// Budgets file [default: <INPUT_DIR>/budgets.json]
+        #[arg(long)]
+        budgets: Option<PathBuf>,
+
+        /// Report on the year up to this date (YYYY-MM-DD) [default: latest transaction]
+        #[arg(long, value_parser = exclusion_policy::parse_date)]
+        as_of: Option<NaiveDate>,
+    },
+    /// Print a draft config.json for a new source from one of its CSV files
+    Detect {
+        /// CSV file downloaded from the bank
+        file: PathBuf,
+    },
+    /// Append the transactions of new input files to the ledger
+    Import,
+    /// Print every category produced by the rules
+    PrintCategories,
+    /// List descriptions not recognized by any rule
+    ListUnknown,
+    /// Print the total for each unrecognized description
+    SumUnknown,
+    /// Print draft rules for significant unrecognized descriptions
+    DraftRules,
+    /// Print draft rules for every PayPal description
+    DraftPaypalRules,
+    /// List raw descriptions and the descriptions they classify to
+    ListDescriptions,
+    /// Print the date range covered by each source
+    Summarize,
+    /// List transactions dropped because overlapping files repeat them
+    Duplicates,
+    /// Print checking and PayPal transactions sorted by description
+    Align,
+    /// List matched transfers between sources and unmatched transfer candidates
+    Transfers,
+}
 
 fn main() {
-    // icicle_chart_data::generate_icicle_chart_data();
-    transaction_extras::print_all_transactions_as_csv();
-    // classification_extras::print_draft_rules_for_unrecognized_descriptions();
-    // print_categories();
-    // sum_categories();
-    // list_unrecognized_descriptions();
-    // sum_unrecognized_descriptions();
+    let cli = Cli::parse();
+    if let Err(error) = run(cli) {
+        eprintln!("{}", error);
+        exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let load_policy = {
+        let (exclusions, input_dir, from, to) = (cli.exclusions.clone(), cli.input_dir.clone(), cli.from, cli.to);
+        move || load_exclusion_policy(exclusions.as_deref(), &input_dir, from, to)
+    };
+    let input_dir = cli.input_dir;
+    let input = InputOptions {
+        rules_path: cli.rules.unwrap_or_else(|| input_dir.join("rules.json")),
+        overrides_path: cli.overrides.or_else(|| {
+            Some(input_dir.join("overrides.json")).filter(|path| path.exists())
+        }),
+        ledger_path: cli.ledger.or_else(|| Some(input_dir.join("ledger.jsonl")).filter(|path| path.exists())),
+        input_dir,
+        transfer_day_window: cli.transfer_days,
+    };
+
+    // Commands write into a buffer, so a failing command leaves an existing output file untouched.
+    let mut out: Vec<u8> = vec![];
+    match cli.command {
+        Command::Icicle { mode, round_to, sort, pretty } => {
+            let options = IcicleOptions { mode, rounding: round_to, sort, pretty };
+            icicle_chart_data::generate_icicle_chart_data(&input, &load_policy()?, options, &mut out)?
+        }
+        Command::Report { mode } => {
+            let options = IcicleOptions {
+                mode,
+                rounding: Rounding::Cents,
+                sort: IcicleSort::Amount,
+                pretty: false,
+            };
+            html_report::generate_html_report(&input, &load_policy()?, options, &mut out)?
+        }
+        Command::Serve { port } => server::serve(&input, &load_policy, port)?,
+        Command::Watch { interval } => {
+            // Watching never finishes, so its reports go straight to the output.
+            let mut out = open_output(cli.output.as_deref())?;
+            let exclusions_path = cli.exclusions.as_deref();
+            watch::watch(&input, &load_policy, exclusions_path, Duration::from_secs(interval), &mut out)?;
+            out.flush()?;
+            return Ok(());
+        }
+        Command::ExportCsv => transaction_extras::print_all_transactions_as_csv(&input, &mut out)?,
+        Command::PrintTransactions => transaction_extras::print_all_transactions(&input, &mut out)?,
+        Command::SumCategories { depth } => classification_extras::sum_categories(
+            &input,
+            &load_policy()?,
+            depth.map(|depth| depth as usize),
+            &mut out,
+        )?,
+        Command::Periods { period, breaks, depth, format } => period_report::print_period_report(
+            &input,
+            &load_policy()?,
+            period,
+            &breaks,
+            depth.map(|depth| depth as usize),
+            format,
+            &mut out,
+        )?,
+        Command::Budget { budgets, as_of } => {
+            let budgets_path = budgets.unwrap_or_else(|| input.input_dir.join("budgets.json"));
+            budgets::print_budget_report(&input, &load_policy()?, &budgets_path, as_of, &mut out)?
+        }
+        Command::Detect { file } => detect::print_draft_config(&file, &mut out)?,
+        Command::Import => {
+            let ledger_path = input.ledger_path.clone().unwrap_or_else(|| input.input_dir.join("ledger.jsonl"));
+            ledger::import(&input, &ledger_path, &mut out)?
+        }
+        Command::PrintCategories => classification_extras::print_categories(&input, &mut out)?,
+        Command::ListUnknown => {
+            classification_extras::list_unrecognized_descriptions(&input, &load_policy()?, &mut out)?
+        }
+        Command::SumUnknown => {
+            classification_extras::sum_unrecognized_descriptions(&input, &load_policy()?, &mut out)?
+        }
+        Command::DraftRules => classification_extras::print_draft_rules_for_unrecognized_descriptions(
+            &input, &load_policy()?, &mut out,
+        )?,
+        Command::DraftPaypalRules => classification_extras::print_draft_rules(&input, &mut out)?,
+        Command::ListDescriptions => classification_extras::list_descriptions(&input, &mut out)?,
+        Command::Summarize => transaction_extras::summarize_transactions(&input, &mut out)?,
+        Command::Duplicates => transaction_extras::print_duplicates(&input, &mut out)?,
+        Command::Align => classification_extras::align_checking_and_paypal(&input, &mut out)?,
+        Command::Transfers => classification_extras::print_transfers(&input, &mut out)?,
+    }
+    let mut output = open_output(cli.output.as_deref())?;
+    output.write_all(&out)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Opens the --output file, or stdout without one.
+fn open_output(output: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?,
+        )),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn load_exclusion_policy(
+    exclusions: Option<&Path>,
+    input_dir: &Path,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<ExclusionPolicy> {
+    let mut policy = match exclusions {
+        Some(path) => read_exclusion_policy(path)?,
+        None => {
+            let default_path = input_dir.join("exclusions.json");
+            if default_path.exists() {
+                read_exclusion_policy(&default_path)?
+            } else {
+                ExclusionPolicy::default()
+            }
+        }
+    };
+    if from.is_some() {
+        policy.start_date = from;
+    }
+    if to.is_some() {
+        policy.end_date = to;
+    }
+    Ok(policy)
 }
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const CURRENCY_SYMBOLS: [char; 4] = ['$', '€', '£', '¥'];
+
+/// An exact amount of money in whole cents. Negative amounts are spending, positive amounts are
+/// income.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money {
+    cents: i64,
+}
+
+/// How finely to round amounts when writing them out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Rounding {
+    Cents,
+    Dollars,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseMoneyError;
+
+impl Money {
+    pub const ZERO: Money = Money { cents: 0 };
+
+    pub fn from_cents(cents: i64) -> Self {
+        Money { cents }
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.cents > 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.cents < 0
+    }
+
+    pub fn abs(self) -> Self {
+        Money { cents: self.cents.abs() }
+    }
+
+    /// Returns `percent` percent of the amount, rounded to the nearest cent.
+    pub fn percent(self, percent: f64) -> Self {
+        Money { cents: (self.cents as f64 * percent / 100.0).round() as i64 }
+    }
+
+    /// Divides into `divisor` equal shares, rounded to the nearest cent.
+    pub fn divide_rounded(self, divisor: i64) -> Self {
+        Money { cents: (self.cents as f64 / divisor as f64).round() as i64 }
+    }
+
+    /// Rounds half away from zero.
+    pub fn round(self, rounding: Rounding) -> Self {
+        match rounding {
+            Rounding::Cents => self,
+            Rounding::Dollars => {
+                let dollars = (self.cents.abs() + 50) / 100;
+                Money { cents: self.cents.signum() * dollars * 100 }
+            }
+        }
+    }
+}
+
+/// Parses currency strings such as "1,234.56", "-$12.34", "$-12.34", "(12.34)" and "12.34-".
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut text = s.trim();
+        let mut negative = false;
+        if let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            negative = true;
+            text = inner.trim();
+        }
+        if let Some(inner) = text.strip_suffix('-') {
+            negative = toggle_sign(negative)?;
+            text = inner.trim();
+        }
+        text = strip_currency_symbol(text);
+        if let Some(inner) = text.strip_prefix('-') {
+            negative = toggle_sign(negative)?;
+            text = inner;
+        } else if let Some(inner) = text.strip_prefix('+') {
+            text = inner;
+        }
+        text = strip_currency_symbol(text.trim());
+
+        let cents = parse_unsigned_cents(&text.replace(',', ""))?;
+        Ok(Money { cents: if negative { -cents } else { cents } })
+    }
+}
+
+fn toggle_sign(negative: bool) -> Result<bool, ParseMoneyError> {
+    if negative { Err(ParseMoneyError) } else { Ok(true) }
+}
+
+fn strip_currency_symbol(text: &str) -> &str {
+    text.trim_start_matches(&CURRENCY_SYMBOLS[..])
+        .trim_end_matches(&CURRENCY_SYMBOLS[..])
+        .trim()
+}
+
+fn parse_unsigned_cents(text: &str) -> Result<i64, ParseMoneyError> {
+    let (whole, fraction) = match text.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (text, ""),
+    };
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(ParseMoneyError);
+    }
+    if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(ParseMoneyError);
+    }
+    if fraction.len() > 2 && fraction[2..].chars().any(|c| c != '0') {
+        return Err(ParseMoneyError);
+    }
+
+    let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| ParseMoneyError)? };
+    let mut fraction_cents = 0;
+    for (i, digit) in fraction.chars().take(2).enumerate() {
+        fraction_cents += digit.to_digit(10).unwrap() as i64 * if i == 0 { 10 } else { 1 };
+    }
+    whole
+        .checked_mul(100)
+        .and_then(|cents| cents.checked_add(fraction_cents))
+        .ok_or(ParseMoneyError)
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.unsigned_abs();
+        let text = format!("{}{}.{:02}", sign, cents / 100, cents % 100);
+        write!(f, "{:>width$}", text, width = f.width().unwrap_or(0))
+    }
+}
+
+impl fmt::Debug for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for ParseMoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid money amount")
+    }
+}
+
+impl std::error::Error for ParseMoneyError {}
+
+/// Serializes as a JSON number: whole dollars as an integer, otherwise with cents.
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.cents % 100 == 0 {
+            serializer.serialize_i64(self.cents / 100)
+        } else {
+            serializer.serialize_f64(self.cents as f64 / 100.0)
+        }
+    }
+}
+
+/// Deserializes from a currency string such as "-12.34" or "$1,000", or from a JSON number.
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+struct MoneyVisitor;
+
+impl<'de> Visitor<'de> for MoneyVisitor {
+    type Value = Money;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an amount of money as a string or number")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Money, E> {
+        value
+            .parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Money, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Money, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Money, E> {
+        self.visit_str(&value.to_string())
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money { cents: self.cents + other.cents }
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.cents += other.cents;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money { cents: self.cents - other.cents }
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        self.cents -= other.cents;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money { cents: -self.cents }
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item=Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item=&'a Money>>(iter: I) -> Money {
+        iter.copied().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Money, ParseMoneyError> {
+        s.parse()
+    }
+
+    #[test]
+    fn parses_plain_amounts() {
+        assert_eq!(parse("12.93"), Ok(Money::from_cents(1293)));
+        assert_eq!(parse("-12.93"), Ok(Money::from_cents(-1293)));
+        assert_eq!(parse("+7"), Ok(Money::from_cents(700)));
+        assert_eq!(parse("0.5"), Ok(Money::from_cents(50)));
+        assert_eq!(parse(".05"), Ok(Money::from_cents(5)));
+        assert_eq!(parse("1,234.50"), Ok(Money::from_cents(123450)));
+        assert_eq!(parse("3.1400"), Ok(Money::from_cents(314)));
+    }
+
+    #[test]
+    fn parses_negative_notations_and_currency_symbols() {
+        assert_eq!(parse("(12.34)"), Ok(Money::from_cents(-1234)));
+        assert_eq!(parse("12.34-"), Ok(Money::from_cents(-1234)));
+        assert_eq!(parse("$12.34"), Ok(Money::from_cents(1234)));
+        assert_eq!(parse("-$12.34"), Ok(Money::from_cents(-1234)));
+        assert_eq!(parse("$-12.34"), Ok(Money::from_cents(-1234)));
+        assert_eq!(parse("($1,000.00)"), Ok(Money::from_cents(-100000)));
+        assert_eq!(parse(" 12.34 € "), Ok(Money::from_cents(1234)));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        assert_eq!(parse(""), Err(ParseMoneyError));
+        assert_eq!(parse("twelve"), Err(ParseMoneyError));
+        assert_eq!(parse("1.234"), Err(ParseMoneyError));
+        assert_eq!(parse("1.2.3"), Err(ParseMoneyError));
+        assert_eq!(parse("(-12.34)"), Err(ParseMoneyError));
+        assert_eq!(parse("-12.34-"), Err(ParseMoneyError));
+    }
+
+    #[test]
+    fn sums_exactly() {
+        let total: Money = std::iter::repeat_n(Money::from_cents(10), 10_000).sum();
+
+        assert_eq!(total, Money::from_cents(100_000));
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(Money::from_cents(1249).round(Rounding::Dollars), Money::from_cents(1200));
+        assert_eq!(Money::from_cents(1250).round(Rounding::Dollars), Money::from_cents(1300));
+        assert_eq!(Money::from_cents(-1250).round(Rounding::Dollars), Money::from_cents(-1300));
+        assert_eq!(Money::from_cents(-1249).round(Rounding::Cents), Money::from_cents(-1249));
+    }
+
+    #[test]
+    fn deserializes_from_strings_and_numbers() {
+        let amounts: Vec<Money> = serde_json::from_str(r#"["(12.34)", -12.34, 500, "$1,000"]"#).unwrap();
+
+        assert_eq!(amounts, vec![
+            Money::from_cents(-1234),
+            Money::from_cents(-1234),
+            Money::from_cents(50000),
+            Money::from_cents(100000),
+        ]);
+        assert!(serde_json::from_str::<Money>("1.234").is_err());
+    }
+
+    #[test]
+    fn displays_with_two_decimals() {
+        assert_eq!(Money::from_cents(-5).to_string(), "-0.05");
+        assert_eq!(Money::from_cents(123456).to_string(), "1234.56");
+        assert_eq!(Money::ZERO.to_string(), "0.00");
+        assert_eq!(format!("{:8}", Money::from_cents(-1249)), "  -12.49");
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use crate::money::Money;
+use crate::transactions::Transaction;
+
+const TRANSACTION_START: &str = "<STMTTRN>";
+const TRANSACTION_END: &str = "</STMTTRN>";
+
+#[derive(Debug)]
+pub enum OfxError {
+    MissingElement { transaction: usize, element: &'static str },
+    Date { transaction: usize, value: String },
+    Amount { transaction: usize, value: String },
+}
+
+impl fmt::Display for OfxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OfxError::MissingElement { transaction, element } => {
+                write!(f, "transaction {} has no {}", transaction, element)
+            }
+            OfxError::Date { transaction, value } => {
+                write!(f, "transaction {}: cannot parse date \"{}\"", transaction, value)
+            }
+            OfxError::Amount { transaction, value } => {
+                write!(f, "transaction {}: cannot parse amount \"{}\"", transaction, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OfxError {}
+
+/// Reads the statement transactions of an OFX or QFX file, in either the SGML flavor, where
+/// elements are not closed, or the XML one. The payee name becomes the raw description (the memo
+/// if there is no name), the memo the notes, and the FITID the bank ID. Everything outside
+/// `<STMTTRN>` aggregates, such as balances and headers, is ignored.
+pub fn parse_ofx(contents: &str) -> Result<Vec<Transaction>, OfxError> {
+    let mut transactions = vec![];
+    let mut rest = contents;
+    while let Some(start) = rest.find(TRANSACTION_START) {
+        rest = &rest[start + TRANSACTION_START.len()..];
+        let end = [rest.find(TRANSACTION_END), rest.find(TRANSACTION_START)]
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+            .unwrap_or(rest.len());
+        transactions.push(statement_transaction(&rest[..end], transactions.len() + 1)?);
+        rest = &rest[end..];
+    }
+    Ok(transactions)
+}
+
+fn statement_transaction(aggregate: &str, number: usize) -> Result<Transaction, OfxError> {
+    let required = |element| {
+        element_value(aggregate, element).ok_or(OfxError::MissingElement { transaction: number, element })
+    };
+
+    let date_value = required("DTPOSTED")?;
+    let date = date_value
+        .get(..8)
+        .and_then(|digits| NaiveDate::parse_from_str(digits, "%Y%m%d").ok())
+        .ok_or_else(|| OfxError::Date { transaction: number, value: date_value.clone() })?;
+
+    let amount_value = required("TRNAMT")?;
+    // Some banks write a decimal comma, which the spec allows.
+    let normalized = if amount_value.contains('.') { amount_value.clone() } else { amount_value.replace(',', ".") };
+    let amount: Money = normalized
+        .parse()
+        .map_err(|_| OfxError::Amount { transaction: number, value: amount_value.clone() })?;
+
+    let memo = element_value(aggregate, "MEMO").unwrap_or_default();
+    let raw_description = match element_value(aggregate, "NAME") {
+        Some(name) => name,
+        None if !memo.is_empty() => memo.clone(),
+        None => return Err(OfxError::MissingElement { transaction: number, element: "NAME" }),
+    };
+
+    Ok(Transaction {
+        date,
+        raw_description,
+        raw_category: "".to_string(),
+        amount,
+        description: "".to_string(),
+        category: "".to_string(),
+        notes: memo,
+        excluded: false,
+        bank_id: required("FITID")?,
+    })
+}
+
+/// The text following `<ELEMENT>` up to the next tag, unescaped and trimmed, or `None` if the
+/// element is missing or empty.
+fn element_value(aggregate: &str, element: &str) -> Option<String> {
+    let start_tag = format!("<{}>", element);
+    let start = aggregate.find(&start_tag)? + start_tag.len();
+    let value = &aggregate[start..];
+    let value = value[..value.find('<').unwrap_or(value.len())].trim();
+    if value.is_empty() {
+        return None;
+    }
+    Some(
+        value
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sgml_statement_transactions() {
+        let contents = "OFXHEADER:100
+DATA:OFXSGML
+
+<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><BANKTRANLIST>
+<DTSTART>20230101
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20230105120000.000[-8:PST]
+<TRNAMT>-12.93
+<FITID>2023010501
+<NAME>ACME FALAFEL &amp; GRILL
+<MEMO>POS PURCHASE
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20230115
+<TRNAMT>1500,00
+<FITID>2023011502
+<MEMO>PAYROLL
+</STMTTRN>
+</BANKTRANLIST><LEDGERBAL><BALAMT>100.00<DTASOF>20230131</LEDGERBAL></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>
+";
+
+        let transactions = parse_ofx(contents).unwrap();
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].date, NaiveDate::from_ymd(2023, 1, 5));
+        assert_eq!(transactions[0].raw_description, "ACME FALAFEL & GRILL");
+        assert_eq!(transactions[0].amount, Money::from_cents(-1293));
+        assert_eq!(transactions[0].notes, "POS PURCHASE");
+        assert_eq!(transactions[0].bank_id, "2023010501");
+        assert_eq!(transactions[1].raw_description, "PAYROLL");
+        assert_eq!(transactions[1].amount, Money::from_cents(150000));
+    }
+
+    #[test]
+    fn parses_xml_and_reports_bad_elements() {
+        let transaction = |amount: &str| {
+            format!(
+                "<STMTTRN><DTPOSTED>20230301</DTPOSTED><TRNAMT>{}</TRNAMT><FITID>9</FITID><NAME>SAFEWAY</NAME></STMTTRN>",
+                amount
+            )
+        };
+
+        let transactions = parse_ofx(&format!("<?xml version=\"1.0\"?><OFX>{}</OFX>", transaction("-80.25"))).unwrap();
+        assert_eq!(transactions[0].raw_description, "SAFEWAY");
+        assert_eq!(transactions[0].amount, Money::from_cents(-8025));
+
+        assert!(matches!(parse_ofx(&transaction("ten")), Err(OfxError::Amount { transaction: 1, .. })));
+        assert!(matches!(
+            parse_ofx("<STMTTRN><DTPOSTED>20230301<TRNAMT>1.00<NAME>X</STMTTRN>"),
+            Err(OfxError::MissingElement { element: "FITID", .. })
+        ));
+    }
+}
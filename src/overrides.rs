@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::exclusion_policy::deserialize_date;
+use crate::money::Money;
+use crate::splits::{check_splits, Split, SplitError};
+use crate::transactions::Transaction;
+
+/// Identifies a transaction from one run to the next by its source, date, amount and raw
+/// description, plus its ordinal among the transactions of that source sharing all four, counted
+/// from 1 in file order.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TransactionId {
+    pub source: String,
+    pub date: NaiveDate,
+    pub amount: Money,
+    pub raw_description: String,
+    pub ordinal: usize,
+}
+
+impl fmt::Display for TransactionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} \"{}\" #{}",
+            self.source, self.date, self.amount, self.raw_description, self.ordinal
+        )
+    }
+}
+
+/// Returns the id of each of a source's transactions, in order.
+pub fn transaction_ids(source: &str, transactions: &[Transaction]) -> Vec<TransactionId> {
+    let mut counts: HashMap<(NaiveDate, Money, &str), usize> = HashMap::new();
+    transactions
+        .iter()
+        .map(|transaction| {
+            let count = counts
+                .entry((transaction.date, transaction.amount, &transaction.raw_description))
+                .or_insert(0);
+            *count += 1;
+            TransactionId {
+                source: source.to_string(),
+                date: transaction.date,
+                amount: transaction.amount,
+                raw_description: transaction.raw_description.clone(),
+                ordinal: *count,
+            }
+        })
+        .collect()
+}
+
+/// A manual correction to exactly one transaction, applied after the classification rules.
+/// Empty fields leave the classification as it is; `splits`, if present, replace the rule's.
+#[derive(Debug, Deserialize)]
+pub struct TransactionOverride {
+    pub source: String,
+    #[serde(deserialize_with = "deserialize_date")]
+    pub date: NaiveDate,
+    pub amount: Money,
+    pub raw_description: String,
+    #[serde(default = "first_ordinal")]
+    pub ordinal: usize,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub exclude: bool,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub splits: Option<Vec<Split>>,
+}
+
+fn first_ordinal() -> usize {
+    1
+}
+
+impl TransactionOverride {
+    pub fn id(&self) -> TransactionId {
+        TransactionId {
+            source: self.source.clone(),
+            date: self.date,
+            amount: self.amount,
+            raw_description: self.raw_description.clone(),
+            ordinal: self.ordinal,
+        }
+    }
+
+    pub fn apply(&self, transaction: &mut Transaction) {
+        if !self.description.is_empty() {
+            transaction.description = self.description.clone();
+        }
+        if !self.category.is_empty() {
+            transaction.category = self.category.clone();
+        }
+        if !self.notes.is_empty() {
+            transaction.notes = self.notes.clone();
+        }
+        transaction.excluded |= self.exclude;
+    }
+}
+
+#[derive(Debug)]
+pub enum OverrideError {
+    Ordinal { index: usize },
+    Duplicate { index: usize, id: TransactionId },
+    Split { index: usize, source: SplitError },
+}
+
+impl fmt::Display for OverrideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverrideError::Ordinal { index } => write!(f, "override {}: ordinal starts at 1", index),
+            OverrideError::Duplicate { index, id } => {
+                write!(f, "override {}: another override already matches {}", index, id)
+            }
+            OverrideError::Split { index, source } => write!(f, "override {}: {}", index, source),
+        }
+    }
+}
+
+impl std::error::Error for OverrideError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OverrideError::Split { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TransactionOverrides {
+    overrides: Vec<TransactionOverride>,
+    indexes_by_id: HashMap<TransactionId, usize>,
+}
+
+impl TransactionOverrides {
+    pub fn new(overrides: Vec<TransactionOverride>) -> Result<Self, OverrideError> {
+        let mut indexes_by_id = HashMap::new();
+        for (index, transaction_override) in overrides.iter().enumerate() {
+            if transaction_override.ordinal == 0 {
+                return Err(OverrideError::Ordinal { index });
+            }
+            if let Some(splits) = &transaction_override.splits {
+                check_splits(splits).map_err(|source| OverrideError::Split { index, source })?;
+            }
+            let id = transaction_override.id();
+            if indexes_by_id.contains_key(&id) {
+                return Err(OverrideError::Duplicate { index, id });
+            }
+            indexes_by_id.insert(id, index);
+        }
+        Ok(TransactionOverrides { overrides, indexes_by_id })
+    }
+
+    /// Returns the index and contents of the override for the transaction with this id, if any.
+    pub fn find(&self, id: &TransactionId) -> Option<(usize, &TransactionOverride)> {
+        self.indexes_by_id
+            .get(id)
+            .map(|&index| (index, &self.overrides[index]))
+    }
+
+    /// Returns the overrides whose index is not in `matched`, which no longer match anything.
+    pub fn unmatched(&self, matched: &[usize]) -> Vec<&TransactionOverride> {
+        self.overrides
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !matched.contains(index))
+            .map(|(_, transaction_override)| transaction_override)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn transaction(day: u32, raw_description: &str, cents: i64) -> Transaction {
+        Transaction {
+            description: "Coffee".to_string(),
+            category: "Dining".to_string(),
+            ..test_transaction(NaiveDate::from_ymd(2023, 4, day), raw_description, cents)
+        }
+    }
+
+    fn overrides(json: &str) -> Result<TransactionOverrides, OverrideError> {
+        TransactionOverrides::new(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn numbers_repeated_transactions_in_order() {
+        let ids = transaction_ids("Card", &[
+            transaction(1, "COFFEE", -450),
+            transaction(1, "COFFEE", -500),
+            transaction(1, "COFFEE", -450),
+        ]);
+
+        let ordinals: Vec<usize> = ids.iter().map(|id| id.ordinal).collect();
+        assert_eq!(ordinals, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn applies_override_to_matching_transaction_only() {
+        let overrides = overrides(r#"[{
+            "source": "Card", "date": "2023-04-01", "amount": "-4.50", "raw_description": "COFFEE",
+            "ordinal": 2, "category": "Gift", "exclude": true, "notes": "For Sam"
+        }]"#).unwrap();
+        let transactions = vec![transaction(1, "COFFEE", -450), transaction(1, "COFFEE", -450)];
+        let ids = transaction_ids("Card", &transactions);
+
+        assert!(overrides.find(&ids[0]).is_none());
+        let (index, transaction_override) = overrides.find(&ids[1]).unwrap();
+        let mut overridden = transactions[1].clone();
+        transaction_override.apply(&mut overridden);
+
+        assert_eq!(overridden.description, "Coffee");
+        assert_eq!(overridden.category, "Gift");
+        assert_eq!(overridden.notes, "For Sam");
+        assert!(overridden.excluded);
+        assert!(overrides.unmatched(&[index]).is_empty());
+        assert_eq!(overrides.unmatched(&[]).len(), 1);
+    }
+
+    #[test]
+    fn rejects_duplicate_overrides_and_zero_ordinals() {
+        let entry = r#"{"source": "Card", "date": "2023-04-01", "amount": -4.5, "raw_description": "COFFEE"}"#;
+
+        assert!(matches!(
+            overrides(&format!("[{}, {}]", entry, entry)),
+            Err(OverrideError::Duplicate { index: 1, .. })
+        ));
+        assert!(matches!(
+            overrides(r#"[{"source": "Card", "date": "2023-04-01", "amount": -4.5, "raw_description": "COFFEE", "ordinal": 0}]"#),
+            Err(OverrideError::Ordinal { index: 0 })
+        ));
+    }
+}
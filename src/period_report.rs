@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use chrono::{Datelike, NaiveDate};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::category_tree::roll_up;
+use crate::error::{Error, Result};
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::file_io::{read_classified_transactions, InputOptions, SourceTransactions};
+use crate::money::Money;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PeriodKind {
+    Month,
+    Quarter,
+    Year,
+    /// Between consecutive dates given with --breaks
+    Custom,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+/// A span of dates, the start inclusive and the end exclusive.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Period {
+    pub label: String,
+    #[serde(serialize_with = "serialize_date")]
+    pub start: NaiveDate,
+    #[serde(serialize_with = "serialize_date")]
+    pub end: NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeriodRow {
+    pub category: String,
+    pub amounts: Vec<Money>,
+    pub total: Money,
+    pub average: Money,
+    /// The change in each period from the period starting a year earlier, if the report has one.
+    pub year_over_year: Vec<Option<Money>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeriodReport {
+    pub periods: Vec<Period>,
+    pub rows: Vec<PeriodRow>,
+    pub totals: PeriodRow,
+}
+
+/// Prints the total of each category in each period, with categories as rows and periods as
+/// columns, followed by each row's total, average per period and year-over-year changes.
+/// Month, quarter and year periods run from the first to the last transaction the policy keeps.
+pub fn print_period_report(
+    input: &InputOptions,
+    policy: &ExclusionPolicy,
+    kind: PeriodKind,
+    breaks: &[NaiveDate],
+    depth: Option<usize>,
+    format: ReportFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    check_breaks(kind, breaks)?;
+    let sources = read_classified_transactions(input)?;
+    let report = period_report(&sources, policy, kind, breaks, depth);
+    match format {
+        ReportFormat::Text => write_text(&report, out),
+        ReportFormat::Csv => write_csv(&report, out),
+        ReportFormat::Json => {
+            writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap())?;
+            Ok(())
+        }
+    }
+}
+
+pub fn period_report(
+    sources: &SourceTransactions,
+    policy: &ExclusionPolicy,
+    kind: PeriodKind,
+    breaks: &[NaiveDate],
+    depth: Option<usize>,
+) -> PeriodReport {
+    let included: Vec<_> = sources
+        .iter()
+        .flat_map(|(_source, transactions)| transactions)
+        .filter(|transaction| !policy.excludes(transaction))
+        .collect();
+    let periods = match kind {
+        PeriodKind::Custom => custom_periods(breaks),
+        _ => {
+            let first = included.iter().map(|transaction| transaction.date).min();
+            let last = included.iter().map(|transaction| transaction.date).max();
+            match (first, last) {
+                (Some(first), Some(last)) => calendar_periods(kind, first, last),
+                _ => vec![],
+            }
+        }
+    };
+
+    let mut amounts_by_category: BTreeMap<String, Vec<Money>> = BTreeMap::new();
+    let mut total_amounts = vec![Money::ZERO; periods.len()];
+    for transaction in included {
+        let index = match periods
+            .iter()
+            .position(|period| period.start <= transaction.date && transaction.date < period.end)
+        {
+            Some(index) => index,
+            None => continue,
+        };
+        let category = match depth {
+            Some(depth) => roll_up(&transaction.category, depth),
+            None => transaction.category.clone(),
+        };
+        amounts_by_category
+            .entry(category)
+            .or_insert_with(|| vec![Money::ZERO; periods.len()])[index] += transaction.amount;
+        total_amounts[index] += transaction.amount;
+    }
+
+    let rows = amounts_by_category
+        .into_iter()
+        .map(|(category, amounts)| period_row(category, amounts, &periods))
+        .collect();
+    let totals = period_row("Total".to_string(), total_amounts, &periods);
+    PeriodReport { periods, rows, totals }
+}
+
+fn period_row(category: String, amounts: Vec<Money>, periods: &[Period]) -> PeriodRow {
+    let total: Money = amounts.iter().sum();
+    let average = if periods.is_empty() {
+        Money::ZERO
+    } else {
+        total.divide_rounded(periods.len() as i64)
+    };
+    let year_over_year = periods
+        .iter()
+        .enumerate()
+        .map(|(index, period)| {
+            year_earlier_index(period, periods).map(|earlier_index| amounts[index] - amounts[earlier_index])
+        })
+        .collect();
+    PeriodRow { category, amounts, total, average, year_over_year }
+}
+
+fn year_earlier_index(period: &Period, periods: &[Period]) -> Option<usize> {
+    periods.iter().position(|earlier| {
+        Some(earlier.start) == year_before(period.start) && Some(earlier.end) == year_before(period.end)
+    })
+}
+
+fn year_before(date: NaiveDate) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(date.year() - 1, date.month(), date.day())
+}
+
+/// Custom periods need at least two distinct breaks, and other periods take none.
+fn check_breaks(kind: PeriodKind, breaks: &[NaiveDate]) -> Result<()> {
+    let distinct: BTreeSet<&NaiveDate> = breaks.iter().collect();
+    match kind {
+        PeriodKind::Custom if distinct.len() < 2 => Err(Error::Periods(
+            "--period custom needs at least two distinct dates in --breaks".to_string(),
+        )),
+        PeriodKind::Custom => Ok(()),
+        _ if !breaks.is_empty() => Err(Error::Periods("--breaks only applies to --period custom".to_string())),
+        _ => Ok(()),
+    }
+}
+
+fn custom_periods(breaks: &[NaiveDate]) -> Vec<Period> {
+    let mut breaks = breaks.to_vec();
+    breaks.sort();
+    breaks.dedup();
+    breaks
+        .windows(2)
+        .map(|window| Period {
+            label: format!("{}..{}", window[0], window[1]),
+            start: window[0],
+            end: window[1],
+        })
+        .collect()
+}
+
+/// The months, quarters or years from the one containing `first` to the one containing `last`.
+fn calendar_periods(kind: PeriodKind, first: NaiveDate, last: NaiveDate) -> Vec<Period> {
+    let months_per_period = match kind {
+        PeriodKind::Month => 1,
+        PeriodKind::Quarter => 3,
+        _ => 12,
+    };
+    let first_month = (first.month0() / months_per_period) * months_per_period;
+    let mut start = NaiveDate::from_ymd(first.year(), first_month + 1, 1);
+    let mut periods = vec![];
+    while start <= last {
+        let end = add_months(start, months_per_period);
+        let label = match kind {
+            PeriodKind::Month => format!("{}-{:02}", start.year(), start.month()),
+            PeriodKind::Quarter => format!("{}-Q{}", start.year(), start.month0() / 3 + 1),
+            _ => start.year().to_string(),
+        };
+        periods.push(Period { label, start, end });
+        start = end;
+    }
+    periods
+}
+
+fn add_months(date: NaiveDate, months: u32) -> NaiveDate {
+    let month0 = date.month0() + months;
+    NaiveDate::from_ymd(date.year() + (month0 / 12) as i32, month0 % 12 + 1, 1)
+}
+
+fn write_text(report: &PeriodReport, out: &mut dyn Write) -> Result<()> {
+    let rows: Vec<&PeriodRow> = report.rows.iter().chain(std::iter::once(&report.totals)).collect();
+
+    let mut labels: Vec<&str> = report.periods.iter().map(|period| period.label.as_str()).collect();
+    labels.extend(&["Total", "Average"]);
+    let table = rows
+        .iter()
+        .map(|row| {
+            let mut cells: Vec<String> = row.amounts.iter().map(Money::to_string).collect();
+            cells.push(row.total.to_string());
+            cells.push(row.average.to_string());
+            (row.category.as_str(), cells)
+        })
+        .collect();
+    write_table(&labels, table, out)?;
+
+    let compared: Vec<usize> = (0..report.periods.len())
+        .filter(|&index| report.totals.year_over_year[index].is_some())
+        .collect();
+    if !compared.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "Change from a year earlier")?;
+        let labels: Vec<&str> = compared.iter().map(|&index| report.periods[index].label.as_str()).collect();
+        let table = rows
+            .iter()
+            .map(|row| {
+                let cells = compared
+                    .iter()
+                    .map(|&index| row.year_over_year[index].unwrap().to_string())
+                    .collect();
+                (row.category.as_str(), cells)
+            })
+            .collect();
+        write_table(&labels, table, out)?;
+    }
+    Ok(())
+}
+
+fn write_table(labels: &[&str], rows: Vec<(&str, Vec<String>)>, out: &mut dyn Write) -> Result<()> {
+    let category_width = rows
+        .iter()
+        .map(|(category, _cells)| category.len())
+        .chain(std::iter::once("Category".len()))
+        .max()
+        .unwrap();
+    let cell_width = labels
+        .iter()
+        .map(|label| label.len())
+        .chain(rows.iter().flat_map(|(_category, cells)| cells.iter().map(String::len)))
+        .max()
+        .unwrap_or(0);
+
+    write!(out, "{:width$}", "Category", width = category_width)?;
+    for label in labels {
+        write!(out, "  {:>width$}", label, width = cell_width)?;
+    }
+    writeln!(out)?;
+    for (category, cells) in rows {
+        write!(out, "{:width$}", category, width = category_width)?;
+        for cell in cells {
+            write!(out, "  {:>width$}", cell, width = cell_width)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_csv(report: &PeriodReport, out: &mut dyn Write) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    let mut header = vec!["Category".to_string()];
+    header.extend(report.periods.iter().map(|period| period.label.clone()));
+    header.push("Total".to_string());
+    header.push("Average".to_string());
+    header.extend(report.periods.iter().map(|period| format!("{} YoY", period.label)));
+    writer.write_record(&header).map_err(std::io::Error::from)?;
+    for row in report.rows.iter().chain(std::iter::once(&report.totals)) {
+        let mut record = vec![row.category.clone()];
+        record.extend(row.amounts.iter().map(Money::to_string));
+        record.push(row.total.to_string());
+        record.push(row.average.to_string());
+        record.extend(row.year_over_year.iter().map(|delta| delta.map_or(String::new(), |delta| delta.to_string())));
+        writer.write_record(&record).map_err(std::io::Error::from)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn serialize_date<S: serde::Serializer>(date: &NaiveDate, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&date.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transactions::{test_transaction, Transaction};
+
+    use super::*;
+
+    fn transaction(year: i32, month: u32, category: &str, cents: i64) -> Transaction {
+        Transaction { category: category.to_string(), ..test_transaction(NaiveDate::from_ymd(year, month, 15), "", cents) }
+    }
+
+    fn sources(transactions: Vec<Transaction>) -> SourceTransactions {
+        vec![("Card".to_string(), transactions)]
+    }
+
+    fn cents(amounts: &[i64]) -> Vec<Money> {
+        amounts.iter().copied().map(Money::from_cents).collect()
+    }
+
+    #[test]
+    fn totals_categories_per_quarter_with_year_over_year_changes() {
+        let sources = sources(vec![
+            transaction(2022, 2, "Food/Groceries", -1000),
+            transaction(2022, 11, "Food/Dining", -500),
+            transaction(2023, 1, "Food/Groceries", -1500),
+            transaction(2023, 3, "Home", -300),
+        ]);
+
+        let report = period_report(&sources, &ExclusionPolicy::default(), PeriodKind::Quarter, &[], Some(1));
+
+        let labels: Vec<&str> = report.periods.iter().map(|period| period.label.as_str()).collect();
+        assert_eq!(labels, vec!["2022-Q1", "2022-Q2", "2022-Q3", "2022-Q4", "2023-Q1"]);
+        let food = &report.rows[0];
+        assert_eq!(food.category, "Food");
+        assert_eq!(food.amounts, cents(&[-1000, 0, 0, -500, -1500]));
+        assert_eq!(food.total, Money::from_cents(-3000));
+        assert_eq!(food.average, Money::from_cents(-600));
+        assert_eq!(food.year_over_year, vec![None, None, None, None, Some(Money::from_cents(-500))]);
+        assert_eq!(report.totals.amounts, cents(&[-1000, 0, 0, -500, -1800]));
+    }
+
+    #[test]
+    fn uses_custom_breaks_and_ignores_transactions_outside_them() {
+        let sources = sources(vec![
+            transaction(2023, 1, "Food", -100),
+            transaction(2023, 5, "Food", -200),
+            transaction(2023, 9, "Food", -400),
+        ]);
+        let breaks = [NaiveDate::from_ymd(2023, 4, 1), NaiveDate::from_ymd(2023, 1, 1), NaiveDate::from_ymd(2023, 6, 1)];
+
+        let report = period_report(&sources, &ExclusionPolicy::default(), PeriodKind::Custom, &breaks, None);
+
+        assert_eq!(report.periods.len(), 2);
+        assert_eq!(report.periods[1].label, "2023-04-01..2023-06-01");
+        assert_eq!(report.rows[0].amounts, cents(&[-100, -200]));
+    }
+
+    #[test]
+    fn rejects_custom_periods_without_two_breaks_and_breaks_without_custom_periods() {
+        let date = NaiveDate::from_ymd(2023, 1, 1);
+
+        assert!(matches!(check_breaks(PeriodKind::Custom, &[]), Err(Error::Periods(_))));
+        assert!(matches!(check_breaks(PeriodKind::Custom, &[date, date]), Err(Error::Periods(_))));
+        assert!(matches!(check_breaks(PeriodKind::Month, &[date]), Err(Error::Periods(_))));
+        assert!(check_breaks(PeriodKind::Custom, &[date, NaiveDate::from_ymd(2023, 7, 1)]).is_ok());
+        assert!(check_breaks(PeriodKind::Year, &[]).is_ok());
+    }
+}
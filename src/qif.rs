@@ -0,0 +1,279 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use crate::money::Money;
+use crate::transactions::Transaction;
+
+/// Account types whose records are transactions.
+const TRANSACTION_TYPES: [&str; 3] = ["bank", "ccard", "cash"];
+/// Types of lists, which are skipped.
+const LIST_TYPES: [&str; 5] = ["cat", "class", "memorized", "prices", "security"];
+
+#[derive(Debug)]
+pub enum QifError {
+    NoType { line: usize },
+    UnsupportedType { line: usize, account_type: String },
+    MissingField { line: usize, field: char },
+    Date { line: usize, value: String },
+    Amount { line: usize, value: String },
+}
+
+impl fmt::Display for QifError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QifError::NoType { line } => write!(f, "line {}: record before any !Type header", line),
+            QifError::UnsupportedType { line, account_type } => {
+                write!(f, "line {}: unsupported account type \"{}\" (expected Bank, CCard or Cash)", line, account_type)
+            }
+            QifError::MissingField { line, field } => {
+                write!(f, "record at line {} has no {} field", line, field)
+            }
+            QifError::Date { line, value } => write!(f, "line {}: cannot parse date \"{}\"", line, value),
+            QifError::Amount { line, value } => write!(f, "line {}: cannot parse amount \"{}\"", line, value),
+        }
+    }
+}
+
+impl std::error::Error for QifError {}
+
+#[derive(Debug, Default)]
+struct SplitLine {
+    category: String,
+    memo: String,
+    amount: Option<Money>,
+}
+
+/// The fields of one record, with the line it starts on.
+#[derive(Debug, Default)]
+struct Record {
+    line: usize,
+    date: Option<(usize, String)>,
+    amount: Option<(usize, String)>,
+    payee: String,
+    memo: String,
+    category: String,
+    splits: Vec<SplitLine>,
+}
+
+/// Reads the bank, credit card and cash account transactions of a QIF file, skipping category
+/// lists and other sections. The payee becomes the raw description (the memo if there is no
+/// payee), the memo the notes and the category, such as `Food:Groceries` or `[Savings]` for a
+/// transfer, the raw category. A split transaction becomes one transaction per split, plus one
+/// for any amount the splits leave over. Dates are parsed with `date_format` if it is not empty,
+/// and otherwise as Quicken's month/day/year, where a two-digit year before 70 is in the 2000s.
+/// Records before any `!Type` header and account types such as `Invst` are errors.
+pub fn parse_qif(contents: &str, date_format: &str) -> Result<Vec<Transaction>, QifError> {
+    let mut transactions = vec![];
+    let mut has_header = false;
+    let mut in_transactions = false;
+    let mut record = Record::default();
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix('!') {
+            if let Some(account_type) = header.strip_prefix("Type:") {
+                let account_type = account_type.trim();
+                let lowercase = account_type.to_lowercase();
+                in_transactions = TRANSACTION_TYPES.contains(&lowercase.as_str());
+                if !in_transactions && !LIST_TYPES.contains(&lowercase.as_str()) {
+                    let account_type = account_type.to_string();
+                    return Err(QifError::UnsupportedType { line: line_number, account_type });
+                }
+                has_header = true;
+            } else if header.starts_with("Account") {
+                in_transactions = false;
+                has_header = true;
+            }
+            record = Record::default();
+            continue;
+        }
+        let mut chars = line.chars();
+        let (code, value) = match chars.next() {
+            Some(code) => (code, chars.as_str().trim().to_string()),
+            None => continue,
+        };
+        if !has_header {
+            return Err(QifError::NoType { line: line_number });
+        }
+        if !in_transactions {
+            continue;
+        }
+        if code == '^' {
+            let finished = std::mem::take(&mut record);
+            if finished.line != 0 {
+                transactions.extend(record_to_transactions(finished, date_format)?);
+            }
+            continue;
+        }
+        if record.line == 0 {
+            record.line = line_number;
+        }
+        match code {
+            'D' => record.date = Some((line_number, value)),
+            'T' | 'U' => record.amount = Some((line_number, value)),
+            'P' => record.payee = value,
+            'M' => record.memo = value,
+            'L' => record.category = value,
+            'S' => record.splits.push(SplitLine { category: value, ..SplitLine::default() }),
+            'E' => {
+                if let Some(split) = record.splits.last_mut() {
+                    split.memo = value;
+                }
+            }
+            '$' => {
+                if let Some(split) = record.splits.last_mut() {
+                    split.amount = Some(parse_amount(line_number, &value)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    if record.line != 0 {
+        transactions.extend(record_to_transactions(record, date_format)?);
+    }
+    Ok(transactions)
+}
+
+fn record_to_transactions(record: Record, date_format: &str) -> Result<Vec<Transaction>, QifError> {
+    let missing = |field| QifError::MissingField { line: record.line, field };
+    let (date_line, date_value) = record.date.as_ref().ok_or_else(|| missing('D'))?;
+    let date = parse_date(date_value, date_format)
+        .ok_or_else(|| QifError::Date { line: *date_line, value: date_value.clone() })?;
+    let (amount_line, amount_value) = record.amount.as_ref().ok_or_else(|| missing('T'))?;
+    let amount = parse_amount(*amount_line, amount_value)?;
+    let raw_description = if record.payee.is_empty() { record.memo.clone() } else { record.payee.clone() };
+
+    let transaction = |amount, raw_category: &str, notes: &str| Transaction {
+        date,
+        raw_description: raw_description.clone(),
+        raw_category: raw_category.to_string(),
+        amount,
+        description: "".to_string(),
+        category: "".to_string(),
+        notes: notes.to_string(),
+        excluded: false,
+        bank_id: "".to_string(),
+    };
+
+    let mut transactions = vec![];
+    let mut remainder = amount;
+    for split in &record.splits {
+        let split_amount = split.amount.unwrap_or(Money::ZERO);
+        let notes = if split.memo.is_empty() { &record.memo } else { &split.memo };
+        transactions.push(transaction(split_amount, &split.category, notes));
+        remainder -= split_amount;
+    }
+    if transactions.is_empty() || remainder != Money::ZERO {
+        transactions.push(transaction(remainder, &record.category, &record.memo));
+    }
+    Ok(transactions)
+}
+
+fn parse_amount(line: usize, value: &str) -> Result<Money, QifError> {
+    value.parse().map_err(|_| QifError::Amount { line, value: value.to_string() })
+}
+
+fn parse_date(value: &str, date_format: &str) -> Option<NaiveDate> {
+    if !date_format.is_empty() {
+        return NaiveDate::parse_from_str(value, date_format).ok();
+    }
+    let normalized: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let parts: Vec<i32> = normalized
+        .split(['/', '\'', '-', '.'])
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let (month, day, year) = match parts[..] {
+        [month, day, year] => (month, day, year),
+        _ => return None,
+    };
+    let year = match year {
+        0..=69 => 2000 + year,
+        70..=99 => 1900 + year,
+        _ => year,
+    };
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_transactions_and_splits() {
+        let contents = "!Type:Cat
+NFood
+^
+!Type:Bank
+D1/ 5'23
+T-1,012.93
+PACME FALAFEL
+MLunch
+LDining
+^
+D01/06/2023
+U-100.00
+PSAFEWAY
+LGroceries
+SGroceries
+$-70.00
+SHousehold:Supplies
+EPaper towels
+$-20.00
+^
+";
+
+        let transactions = parse_qif(contents, "").unwrap();
+
+        let parts: Vec<(NaiveDate, &str, &str, Money, &str)> = transactions
+            .iter()
+            .map(|transaction| {
+                (
+                    transaction.date,
+                    transaction.raw_description.as_str(),
+                    transaction.raw_category.as_str(),
+                    transaction.amount,
+                    transaction.notes.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(parts, vec![
+            (NaiveDate::from_ymd(2023, 1, 5), "ACME FALAFEL", "Dining", Money::from_cents(-101293), "Lunch"),
+            (NaiveDate::from_ymd(2023, 1, 6), "SAFEWAY", "Groceries", Money::from_cents(-7000), ""),
+            (NaiveDate::from_ymd(2023, 1, 6), "SAFEWAY", "Household:Supplies", Money::from_cents(-2000), "Paper towels"),
+            (NaiveDate::from_ymd(2023, 1, 6), "SAFEWAY", "Groceries", Money::from_cents(-1000), ""),
+        ]);
+    }
+
+    #[test]
+    fn keeps_last_record_without_terminator() {
+        let transactions = parse_qif("!Type:Bank\nD1/5/23\nT-1.00\nPACME\n^\nD1/6/23\nT-2.00\nPSAFEWAY\n", "").unwrap();
+
+        let descriptions: Vec<&str> = transactions.iter().map(|transaction| transaction.raw_description.as_str()).collect();
+        assert_eq!(descriptions, vec!["ACME", "SAFEWAY"]);
+    }
+
+    #[test]
+    fn uses_date_format_and_reports_bad_records() {
+        let transactions = parse_qif("!Type:CCard\nD2023-03-01\nT12.00\nPREFUND\n^\n", "%Y-%m-%d").unwrap();
+        assert_eq!(transactions[0].date, NaiveDate::from_ymd(2023, 3, 1));
+
+        assert!(matches!(
+            parse_qif("!Type:CCard\nD13/45/23\nT1.00\n^\n", ""),
+            Err(QifError::Date { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_qif("!Type:CCard\nPNO DATE\nT1.00\n^\n", ""),
+            Err(QifError::MissingField { line: 2, field: 'D' })
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_and_unsupported_types() {
+        assert!(matches!(parse_qif("D1/5/23\nT-1.00\n^\n", ""), Err(QifError::NoType { line: 1 })));
+        assert!(matches!(
+            parse_qif("!Type:Cat\nNFood\n^\n!Type:Invst\nD1/5/23\n^\n", ""),
+            Err(QifError::UnsupportedType { line: 4, .. })
+        ));
+    }
+}
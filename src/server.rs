@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::category_tree::is_within;
+use crate::error::{Error, Result};
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::file_io::{read_classified_transactions, InputOptions};
+use crate::html_report::html_report;
+use crate::icicle_chart_data::{icicle_chart_data, IcicleMode, IcicleOptions, IcicleSort};
+use crate::money::{Money, Rounding};
+
+/// A classified transaction as listed by the transactions endpoint.
+#[derive(Debug, Serialize)]
+struct TransactionView {
+    source: String,
+    date: String,
+    raw_description: String,
+    raw_category: String,
+    amount: Money,
+    description: String,
+    category: String,
+    notes: String,
+    excluded: bool,
+}
+
+/// Serves the report page and its data on localhost until killed, re-reading the input directory,
+/// rules, overrides and exclusion policy for every request so edits show up on reload:
+///
+/// - `/` is the HTML report, taking `mode`.
+/// - `/icicle.json` is the icicle chart data, taking `mode`, `sort` and `round_to`.
+/// - `/transactions.json` lists classified transactions, filtered by any of `source`, `category`
+///   (including subcategories), `description` and `excluded` ("true" or "false").
+pub fn serve(
+    input: &InputOptions,
+    load_policy: &dyn Fn() -> Result<ExclusionPolicy>,
+    port: u16,
+) -> Result<()> {
+    let address = format!("127.0.0.1:{}", port);
+    let server = Server::http(&address).map_err(|source| Error::Serve { address: address.clone(), source })?;
+    eprintln!("Serving on http://{}/", address);
+    for request in server.incoming_requests() {
+        let response = match respond(input, load_policy, request.url()) {
+            Ok(response) => response,
+            Err(error) => text_response(500, &error.to_string()),
+        };
+        respond_or_warn(request, response);
+    }
+    Ok(())
+}
+
+type HttpResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+fn respond(
+    input: &InputOptions,
+    load_policy: &dyn Fn() -> Result<ExclusionPolicy>,
+    url: &str,
+) -> Result<HttpResponse> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let query = parse_query(query);
+    let options = match icicle_options(&query) {
+        Ok(options) => options,
+        Err(message) => return Ok(text_response(400, &message)),
+    };
+    let response = match path {
+        "/" => {
+            let policy = load_policy()?;
+            let sources = read_classified_transactions(input)?;
+            content_response(html_report(&sources, &policy, options), "text/html; charset=utf-8")
+        }
+        "/icicle.json" => {
+            let policy = load_policy()?;
+            let sources = read_classified_transactions(input)?;
+            json_response(&icicle_chart_data(&sources, &policy, options))
+        }
+        "/transactions.json" => {
+            let policy = load_policy()?;
+            let mut views = vec![];
+            for (source, transactions) in read_classified_transactions(input)? {
+                for transaction in transactions {
+                    let excluded = policy.excludes(&transaction);
+                    let view = TransactionView {
+                        source: source.clone(),
+                        date: transaction.date.to_string(),
+                        raw_description: transaction.raw_description,
+                        raw_category: transaction.raw_category,
+                        amount: transaction.amount,
+                        description: transaction.description,
+                        category: transaction.category,
+                        notes: transaction.notes,
+                        excluded,
+                    };
+                    if view_matches(&view, &query) {
+                        views.push(view);
+                    }
+                }
+            }
+            json_response(&views)
+        }
+        _ => text_response(404, "Not found"),
+    };
+    Ok(response)
+}
+
+fn icicle_options(query: &HashMap<String, String>) -> std::result::Result<IcicleOptions, String> {
+    Ok(IcicleOptions {
+        mode: query_value(query, "mode", IcicleMode::Spending)?,
+        rounding: query_value(query, "round_to", Rounding::Cents)?,
+        sort: query_value(query, "sort", IcicleSort::Amount)?,
+        pretty: false,
+    })
+}
+
+fn query_value<T: ValueEnum>(
+    query: &HashMap<String, String>,
+    key: &str,
+    default: T,
+) -> std::result::Result<T, String> {
+    match query.get(key) {
+        Some(value) => T::from_str(value, true).map_err(|_| format!("invalid {}: {}", key, value)),
+        None => Ok(default),
+    }
+}
+
+fn view_matches(view: &TransactionView, query: &HashMap<String, String>) -> bool {
+    query.get("source").is_none_or(|source| &view.source == source)
+        && query.get("category").is_none_or(|category| is_within(&view.category, category))
+        && query.get("description").is_none_or(|description| &view.description == description)
+        && query.get("excluded").is_none_or(|excluded| view.excluded.to_string() == *excluded)
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_query_component(key), decode_query_component(value))
+        })
+        .collect()
+}
+
+/// Decodes `+` and `%XX` escapes, keeping malformed escapes as they are.
+fn decode_query_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit() =>
+            {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn json_response<T: Serialize>(value: &T) -> HttpResponse {
+    content_response(serde_json::to_string(value).unwrap(), "application/json")
+}
+
+fn content_response(body: String, content_type: &str) -> HttpResponse {
+    let header = Header::from_bytes("Content-Type", content_type).unwrap();
+    Response::from_string(body).with_header(header)
+}
+
+fn text_response(status: u16, message: &str) -> HttpResponse {
+    content_response(message.to_string(), "text/plain; charset=utf-8").with_status_code(status)
+}
+
+fn respond_or_warn(request: Request, response: HttpResponse) {
+    let url = request.url().to_string();
+    if let Err(error) = request.respond(response) {
+        eprintln!("warning: cannot respond to {}: {}", url, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_decodes_query() {
+        let query = parse_query("category=Home%2FUtilities&description=Puget+Sound&bad=%zz&trailing=%4");
+
+        assert_eq!(query["category"], "Home/Utilities");
+        assert_eq!(query["description"], "Puget Sound");
+        assert_eq!(query["bad"], "%zz");
+        assert_eq!(query["trailing"], "%4");
+    }
+
+    #[test]
+    fn reads_icicle_options_from_query() {
+        let options = icicle_options(&parse_query("mode=net&sort=name")).unwrap();
+
+        assert_eq!(options.mode, IcicleMode::Net);
+        assert_eq!(options.sort, IcicleSort::Name);
+        assert_eq!(options.rounding, Rounding::Cents);
+        assert!(icicle_options(&parse_query("mode=sideways")).is_err());
+    }
+}
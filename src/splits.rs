@@ -0,0 +1,159 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::money::Money;
+use crate::transactions::Transaction;
+
+/// One part of a transaction split across categories. A part takes either a fixed `amount`, given
+/// as a magnitude that takes the transaction's sign, or a `percentage` of the transaction amount.
+/// Whatever the parts leave over keeps the transaction's own description and category.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Split {
+    #[serde(default)]
+    pub amount: Option<Money>,
+    #[serde(default)]
+    pub percentage: Option<f64>,
+    /// Defaults to the transaction's description.
+    #[serde(default)]
+    pub description: String,
+    pub category: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SplitError {
+    NoShare,
+    MultipleShares,
+    Percentage,
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SplitError::NoShare => write!(f, "split needs one of amount or percentage"),
+            SplitError::MultipleShares => write!(f, "split has both amount and percentage"),
+            SplitError::Percentage => write!(f, "split percentages must add up to between 0 and 100"),
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+pub fn check_splits(splits: &[Split]) -> Result<(), SplitError> {
+    let mut total_percentage = 0.0;
+    for split in splits {
+        match (split.amount, split.percentage) {
+            (None, None) => return Err(SplitError::NoShare),
+            (Some(_), Some(_)) => return Err(SplitError::MultipleShares),
+            (None, Some(percentage)) if percentage < 0.0 => return Err(SplitError::Percentage),
+            (None, Some(percentage)) => total_percentage += percentage,
+            (Some(_), None) => {}
+        }
+    }
+    if total_percentage > 100.0 {
+        return Err(SplitError::Percentage);
+    }
+    Ok(())
+}
+
+/// Splits a classified transaction into one transaction per part, followed by the remainder if
+/// any. Parts are taken in order and each is capped at what is left, so a fixed amount larger
+/// than the transaction takes all of it.
+pub fn split_transaction(transaction: Transaction, splits: &[Split]) -> Vec<Transaction> {
+    if splits.is_empty() {
+        return vec![transaction];
+    }
+    let mut parts = vec![];
+    let mut remainder = transaction.amount;
+    for split in splits {
+        let share = match (split.amount, split.percentage) {
+            (Some(amount), _) => amount.abs(),
+            (None, Some(percentage)) => transaction.amount.abs().percent(percentage),
+            (None, None) => Money::ZERO,
+        };
+        let share = share.min(remainder.abs());
+        let amount = if transaction.amount.is_negative() { -share } else { share };
+        if amount == Money::ZERO {
+            continue;
+        }
+        remainder -= amount;
+        let mut part = transaction.clone();
+        part.amount = amount;
+        if !split.description.is_empty() {
+            part.description = split.description.clone();
+        }
+        part.category = split.category.clone();
+        parts.push(part);
+    }
+    if remainder != Money::ZERO {
+        parts.push(Transaction { amount: remainder, ..transaction });
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn costco(cents: i64) -> Transaction {
+        Transaction {
+            description: "Costco".to_string(),
+            category: "Groceries".to_string(),
+            ..test_transaction(NaiveDate::from_ymd(2023, 3, 4), "COSTCO WHSE #0001", cents)
+        }
+    }
+
+    fn split(amount: Option<i64>, percentage: Option<f64>, category: &str) -> Split {
+        Split {
+            amount: amount.map(Money::from_cents),
+            percentage,
+            category: category.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn splits_by_amount_and_percentage_leaving_remainder_in_original_category() {
+        let parts = split_transaction(costco(-10001), &[
+            split(Some(2000), None, "Household"),
+            split(None, Some(25.0), "Clothing"),
+        ]);
+
+        let summary: Vec<(Money, &str, &str)> = parts
+            .iter()
+            .map(|part| (part.amount, part.description.as_str(), part.category.as_str()))
+            .collect();
+        assert_eq!(summary, vec![
+            (Money::from_cents(-2000), "Costco", "Household"),
+            (Money::from_cents(-2500), "Costco", "Clothing"),
+            (Money::from_cents(-5501), "Costco", "Groceries"),
+        ]);
+    }
+
+    #[test]
+    fn caps_parts_at_transaction_amount() {
+        let parts = split_transaction(costco(1500), &[
+            split(Some(2000), None, "Household"),
+            split(None, Some(50.0), "Clothing"),
+        ]);
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].amount, Money::from_cents(1500));
+        assert_eq!(parts[0].category, "Household");
+    }
+
+    #[test]
+    fn rejects_invalid_splits() {
+        assert_eq!(check_splits(&[split(None, None, "Household")]), Err(SplitError::NoShare));
+        assert_eq!(check_splits(&[split(Some(1), Some(1.0), "Household")]), Err(SplitError::MultipleShares));
+        assert_eq!(
+            check_splits(&[split(None, Some(60.0), "Household"), split(None, Some(50.0), "Clothing")]),
+            Err(SplitError::Percentage)
+        );
+        assert_eq!(check_splits(&[split(Some(1), None, "Household")]), Ok(()));
+    }
+}
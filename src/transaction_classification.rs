@@ -1,46 +1,220 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::str;
 
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
 use serde::Deserialize;
 use trie_rs::{Trie, TrieBuilder};
 
+use crate::exclusion_policy::deserialize_optional_date;
+use crate::money::Money;
+use crate::splits::{check_splits, Split, SplitError};
 use crate::transactions::Transaction;
 
-#[derive(Debug, Deserialize)]
+/// Classifies transactions whose raw description matches. Each rule has exactly one of
+/// `raw_prefix`, `raw_suffix`, `raw_contains` or `raw_regex`, optionally matched ignoring case,
+/// and may further require a source, raw category, sign, amount range (inclusive, spending
+/// negative) and date range (start inclusive, end exclusive).
+///
+/// When several rules match, the one with the most of those predicates wins. Among equally
+/// specific rules, a prefix rule beats a suffix rule, which beats a contains rule, which beats a
+/// regex rule; then the longest pattern wins, then a case-sensitive rule, then the rule that
+/// comes last in the file.
+///
+/// A rule may also split matching transactions into parts with their own categories.
+#[derive(Debug, Default, Deserialize)]
 pub struct TransactionClassificationRule {
+    #[serde(default)]
     pub raw_prefix: String,
+    #[serde(default)]
+    pub raw_suffix: String,
+    #[serde(default)]
+    pub raw_contains: String,
+    #[serde(default)]
+    pub raw_regex: String,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub raw_category: String,
+    #[serde(default)]
+    pub sign: Option<Sign>,
+    #[serde(default)]
+    pub min_amount: Option<Money>,
+    #[serde(default)]
+    pub max_amount: Option<Money>,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub start_date: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub end_date: Option<NaiveDate>,
     pub description: String,
     pub category: String,
+    #[serde(default)]
+    pub splits: Vec<Split>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sign {
+    Positive,
+    Negative,
+}
+
+impl TransactionClassificationRule {
+    fn predicates_match(&self, source: &str, transaction: &Transaction) -> bool {
+        (self.source.is_empty() || self.source == source)
+            && (self.raw_category.is_empty() || self.raw_category == transaction.raw_category)
+            && match self.sign {
+                Some(Sign::Positive) => transaction.amount.is_positive(),
+                Some(Sign::Negative) => transaction.amount.is_negative(),
+                None => true,
+            }
+            && self.min_amount.is_none_or(|min_amount| transaction.amount >= min_amount)
+            && self.max_amount.is_none_or(|max_amount| transaction.amount <= max_amount)
+            && self.start_date.is_none_or(|start_date| transaction.date >= start_date)
+            && self.end_date.is_none_or(|end_date| transaction.date < end_date)
+    }
+
+    fn predicate_count(&self) -> usize {
+        [
+            !self.source.is_empty(),
+            !self.raw_category.is_empty(),
+            self.sign.is_some(),
+            self.min_amount.is_some(),
+            self.max_amount.is_some(),
+            self.start_date.is_some(),
+            self.end_date.is_some(),
+        ]
+            .iter()
+            .filter(|present| **present)
+            .count()
+    }
+
+    /// Ranks how specifically the rule's pattern matches: its kind, then its length, then
+    /// case-sensitivity.
+    fn pattern_rank(&self) -> (u8, usize, bool) {
+        let case_sensitive = !self.case_insensitive;
+        if !self.raw_prefix.is_empty() {
+            (3, self.raw_prefix.len(), case_sensitive)
+        } else if !self.raw_suffix.is_empty() {
+            (2, self.raw_suffix.len(), case_sensitive)
+        } else if !self.raw_contains.is_empty() {
+            (1, self.raw_contains.len(), case_sensitive)
+        } else {
+            (0, 0, case_sensitive)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RuleError {
+    NoPattern { index: usize },
+    MultiplePatterns { index: usize },
+    Regex { index: usize, source: regex::Error },
+    Split { index: usize, source: SplitError },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::NoPattern { index } => write!(
+                f,
+                "rule {} needs one of raw_prefix, raw_suffix, raw_contains or raw_regex",
+                index
+            ),
+            RuleError::MultiplePatterns { index } => write!(
+                f,
+                "rule {} has more than one of raw_prefix, raw_suffix, raw_contains and raw_regex",
+                index
+            ),
+            RuleError::Regex { index, source } => write!(f, "rule {}: {}", index, source),
+            RuleError::Split { index, source } => write!(f, "rule {}: {}", index, source),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RuleError::Regex { source, .. } => Some(source),
+            RuleError::Split { source, .. } => Some(source),
+            _ => None,
+        }
+    }
 }
 
 pub struct TransactionClassifier {
+    rules: Vec<TransactionClassificationRule>,
     prefixes: Trie<u8>,
-    rules_by_prefix: HashMap<String, TransactionClassificationRule>,
+    rule_indexes_by_prefix: HashMap<String, Vec<usize>>,
+    case_insensitive_prefixes: Trie<u8>,
+    rule_indexes_by_case_insensitive_prefix: HashMap<String, Vec<usize>>,
+    suffix_rule_indexes: Vec<usize>,
+    contains_rule_indexes: Vec<usize>,
+    regexes: Vec<(Regex, usize)>,
 }
 
 impl TransactionClassifier {
-    pub fn new(rules: Vec<TransactionClassificationRule>) -> Self {
-        let mut trie_builder = TrieBuilder::new();
-        let mut rules_by_prefix = HashMap::with_capacity(rules.len());
-        for rule in rules {
-            trie_builder.push(rule.raw_prefix.clone());
-            rules_by_prefix.insert(rule.raw_prefix.clone(), rule);
-        }
+    pub fn new(rules: Vec<TransactionClassificationRule>) -> Result<Self, RuleError> {
+        let mut rule_indexes_by_prefix: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut rule_indexes_by_case_insensitive_prefix: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut suffix_rule_indexes = vec![];
+        let mut contains_rule_indexes = vec![];
+        let mut regexes = vec![];
+        for (index, rule) in rules.iter().enumerate() {
+            let patterns = [&rule.raw_prefix, &rule.raw_suffix, &rule.raw_contains, &rule.raw_regex];
+            match patterns.iter().filter(|pattern| !pattern.is_empty()).count() {
+                0 => return Err(RuleError::NoPattern { index }),
+                1 => {}
+                _ => return Err(RuleError::MultiplePatterns { index }),
+            }
+            check_splits(&rule.splits).map_err(|source| RuleError::Split { index, source })?;
 
-        TransactionClassifier {
-            prefixes: trie_builder.build(),
-            rules_by_prefix,
+            if !rule.raw_prefix.is_empty() {
+                if rule.case_insensitive {
+                    let prefix = rule.raw_prefix.to_uppercase();
+                    rule_indexes_by_case_insensitive_prefix.entry(prefix).or_default().push(index);
+                } else {
+                    rule_indexes_by_prefix.entry(rule.raw_prefix.clone()).or_default().push(index);
+                }
+            } else if !rule.raw_suffix.is_empty() {
+                suffix_rule_indexes.push(index);
+            } else if !rule.raw_contains.is_empty() {
+                contains_rule_indexes.push(index);
+            } else {
+                let regex = RegexBuilder::new(&rule.raw_regex)
+                    .case_insensitive(rule.case_insensitive)
+                    .build()
+                    .map_err(|source| RuleError::Regex { index, source })?;
+                regexes.push((regex, index));
+            }
         }
+
+        Ok(TransactionClassifier {
+            rules,
+            prefixes: build_trie(rule_indexes_by_prefix.keys()),
+            rule_indexes_by_prefix,
+            case_insensitive_prefixes: build_trie(rule_indexes_by_case_insensitive_prefix.keys()),
+            rule_indexes_by_case_insensitive_prefix,
+            suffix_rule_indexes,
+            contains_rule_indexes,
+            regexes,
+        })
     }
 
-    pub fn classify_transaction(&self, mut transaction: Transaction) -> Transaction {
-        if let Some(prefix) =
-            get_longest_common_prefix(&transaction.raw_description, &self.prefixes)
-        {
-            if let Some(rule) = self.rules_by_prefix.get(&prefix) {
-                transaction.description = rule.description.clone();
-                transaction.category = rule.category.clone();
-            }
+    /// Classifies the transaction, also returning the splits of the matching rule.
+    pub fn classify_transaction(
+        &self,
+        source: &str,
+        mut transaction: Transaction,
+    ) -> (Transaction, &[Split]) {
+        let mut splits: &[Split] = &[];
+        if let Some(rule) = self.find_rule(source, &transaction) {
+            transaction.description = rule.description.clone();
+            transaction.category = rule.category.clone();
+            splits = &rule.splits;
         } else if transaction.raw_description.is_empty() {
             transaction.description = "Unknown".to_string();
             transaction.category = "Unknown".to_string();
@@ -48,27 +222,115 @@ impl TransactionClassifier {
             transaction.description = transaction.raw_description.clone();
             transaction.category = "Unknown".to_string();
         }
-        transaction
+        (transaction, splits)
+    }
+
+    fn find_rule(&self, source: &str, transaction: &Transaction) -> Option<&TransactionClassificationRule> {
+        self.pattern_matching_rule_indexes(&transaction.raw_description)
+            .into_iter()
+            .filter(|&index| self.rules[index].predicates_match(source, transaction))
+            .max_by_key(|&index| {
+                let rule = &self.rules[index];
+                (rule.predicate_count(), rule.pattern_rank(), index)
+            })
+            .map(|index| &self.rules[index])
+    }
+
+    fn pattern_matching_rule_indexes(&self, raw_description: &str) -> Vec<usize> {
+        if raw_description.is_empty() {
+            return vec![];
+        }
+        let uppercase_description = raw_description.to_uppercase();
+
+        let mut indexes = vec![];
+        for prefix in get_common_prefixes(raw_description, &self.prefixes) {
+            indexes.extend(&self.rule_indexes_by_prefix[&prefix]);
+        }
+        for prefix in get_common_prefixes(&uppercase_description, &self.case_insensitive_prefixes) {
+            indexes.extend(&self.rule_indexes_by_case_insensitive_prefix[&prefix]);
+        }
+        for &index in &self.suffix_rule_indexes {
+            let rule = &self.rules[index];
+            let (description, suffix) =
+                case_adjusted(rule, &rule.raw_suffix, raw_description, &uppercase_description);
+            if description.ends_with(&*suffix) {
+                indexes.push(index);
+            }
+        }
+        for &index in &self.contains_rule_indexes {
+            let rule = &self.rules[index];
+            let (description, pattern) =
+                case_adjusted(rule, &rule.raw_contains, raw_description, &uppercase_description);
+            if description.contains(&*pattern) {
+                indexes.push(index);
+            }
+        }
+        for (regex, index) in &self.regexes {
+            if regex.is_match(raw_description) {
+                indexes.push(*index);
+            }
+        }
+        indexes
     }
 }
 
-fn get_longest_common_prefix(string: &str, prefixes: &Trie<u8>) -> Option<String> {
+/// Returns the description and pattern to compare, uppercased if the rule ignores case.
+fn case_adjusted<'a>(
+    rule: &TransactionClassificationRule,
+    pattern: &str,
+    raw_description: &'a str,
+    uppercase_description: &'a str,
+) -> (&'a str, String) {
+    if rule.case_insensitive {
+        (uppercase_description, pattern.to_uppercase())
+    } else {
+        (raw_description, pattern.to_string())
+    }
+}
+
+/// Pushes the prefixes in sorted order, since trie_rs 0.1 loses a prefix pushed after a longer
+/// string that starts with it.
+fn build_trie<'a, I: Iterator<Item=&'a String>>(prefixes: I) -> Trie<u8> {
+    let mut prefixes: Vec<&String> = prefixes.collect();
+    prefixes.sort();
+    let mut trie_builder = TrieBuilder::new();
+    for prefix in prefixes {
+        trie_builder.push(prefix);
+    }
+    trie_builder.build()
+}
+
+fn get_common_prefixes(string: &str, prefixes: &Trie<u8>) -> Vec<String> {
     prefixes
         .common_prefix_search(string)
         .iter()
-        .map(|utf8_prefix| str::from_utf8(utf8_prefix).unwrap())
-        .max_by(|prefix1, prefix2| prefix1.len().cmp(&prefix2.len()))
-        .map(|prefix| prefix.to_string())
+        .map(|utf8_prefix| str::from_utf8(utf8_prefix).unwrap().to_string())
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::NaiveDate;
+    use crate::transactions::test_transaction;
 
     use super::*;
 
+    fn classify(rules: Vec<TransactionClassificationRule>, raw_description: &str) -> Transaction {
+        TransactionClassifier::new(rules)
+            .unwrap()
+            .classify_transaction("Card", test_transaction(NaiveDate::from_ymd(1, 1, 1), raw_description, 0))
+            .0
+    }
+
+    fn rule(description: &str) -> TransactionClassificationRule {
+        TransactionClassificationRule {
+            description: description.to_string(),
+            category: "Test".to_string(),
+            ..Default::default()
+        }
+    }
+
     #[test]
-    fn gets_longest_common_prefix() {
+    fn gets_common_prefixes() {
         let mut builder = TrieBuilder::new();
         builder.push("AB");
         builder.push("ABC");
@@ -76,10 +338,17 @@ mod tests {
         builder.push("ABCE");
         let trie = builder.build();
 
-        assert_eq!(
-            get_longest_common_prefix("ABCD", &trie),
-            Some("ABC".to_string())
-        );
+        let mut prefixes = get_common_prefixes("ABCD", &trie);
+        prefixes.sort();
+        assert_eq!(prefixes, vec!["AB".to_string(), "ABC".to_string()]);
+    }
+
+    #[test]
+    fn builds_trie_that_keeps_prefixes_of_longer_strings() {
+        let prefixes = ["AMAZON MKTPLACE".to_string(), "AMAZON".to_string()];
+        let trie = build_trie(prefixes.iter());
+
+        assert_eq!(get_common_prefixes("AMAZON", &trie), vec!["AMAZON".to_string()]);
     }
 
     #[test]
@@ -88,16 +357,10 @@ mod tests {
             raw_prefix: "DWB*".to_string(),
             description: "Doctors without Borders".to_string(),
             category: "Donation".to_string(),
-        }]);
+            ..Default::default()
+        }]).unwrap();
 
-        let transaction = classifier.classify_transaction(Transaction {
-            date: NaiveDate::from_ymd(1, 1, 1),
-            raw_description: "DWB*DOCTORS W/O BORDER 212-679-6800 NY".to_string(),
-            raw_category: "".to_string(),
-            amount: 0.0,
-            description: "".to_string(),
-            category: "".to_string(),
-        });
+        let (transaction, _splits) = classifier.classify_transaction("Card", test_transaction(NaiveDate::from_ymd(1, 1, 1), "DWB*DOCTORS W/O BORDER 212-679-6800 NY", 0));
 
         assert_eq!(transaction.description, "Doctors without Borders");
         assert_eq!(transaction.category, "Donation");
@@ -109,16 +372,10 @@ mod tests {
             raw_prefix: "DWB*".to_string(),
             description: "Doctors without Borders".to_string(),
             category: "Donation".to_string(),
-        }]);
+            ..Default::default()
+        }]).unwrap();
 
-        let transaction = classifier.classify_transaction(Transaction {
-            date: NaiveDate::from_ymd(1, 1, 1),
-            raw_description: "ACME FALAFEL".to_string(),
-            raw_category: "".to_string(),
-            amount: 0.0,
-            description: "".to_string(),
-            category: "".to_string(),
-        });
+        let (transaction, _splits) = classifier.classify_transaction("Card", test_transaction(NaiveDate::from_ymd(1, 1, 1), "ACME FALAFEL", 0));
 
         assert_eq!(transaction.description, "ACME FALAFEL");
         assert_eq!(transaction.category, "Unknown");
@@ -130,18 +387,191 @@ mod tests {
             raw_prefix: "DWB*".to_string(),
             description: "Doctors without Borders".to_string(),
             category: "Donation".to_string(),
-        }]);
+            ..Default::default()
+        }]).unwrap();
 
-        let transaction = classifier.classify_transaction(Transaction {
-            date: NaiveDate::from_ymd(1, 1, 1),
-            raw_description: "".to_string(),
-            raw_category: "".to_string(),
-            amount: 0.0,
-            description: "".to_string(),
-            category: "".to_string(),
-        });
+        let (transaction, _splits) = classifier.classify_transaction("Card", test_transaction(NaiveDate::from_ymd(1, 1, 1), "", 0));
 
         assert_eq!(transaction.description, "Unknown");
         assert_eq!(transaction.category, "Unknown");
     }
+
+    #[test]
+    fn matches_contains_suffix_and_regex_rules() {
+        let rules = || vec![
+            TransactionClassificationRule { raw_contains: "NETFLIX".to_string(), ..rule("Netflix") },
+            TransactionClassificationRule { raw_suffix: " SEATTLE WA".to_string(), ..rule("Seattle") },
+            TransactionClassificationRule { raw_regex: r"^SQ \*\w+ COFFEE".to_string(), ..rule("Coffee") },
+        ];
+
+        assert_eq!(classify(rules(), "PAYPAL *NETFLIX 4029357733").description, "Netflix");
+        assert_eq!(classify(rules(), "ACME FALAFEL SEATTLE WA").description, "Seattle");
+        assert_eq!(classify(rules(), "SQ *BLUE COFFEE").description, "Coffee");
+        assert_eq!(classify(rules(), "SQ *COFFEE").category, "Unknown");
+    }
+
+    #[test]
+    fn matches_ignoring_case() {
+        let rules = || vec![
+            TransactionClassificationRule {
+                raw_prefix: "Acme".to_string(),
+                case_insensitive: true,
+                ..rule("Acme")
+            },
+            TransactionClassificationRule {
+                raw_regex: "falafel".to_string(),
+                case_insensitive: true,
+                ..rule("Falafel")
+            },
+        ];
+
+        assert_eq!(classify(rules(), "ACME HARDWARE").description, "Acme");
+        assert_eq!(classify(rules(), "BEST FALAFEL").description, "Falafel");
+    }
+
+    #[test]
+    fn prefers_prefix_then_suffix_then_contains_then_regex() {
+        let rules = || vec![
+            TransactionClassificationRule { raw_regex: "COFFEE".to_string(), ..rule("Regex") },
+            TransactionClassificationRule { raw_contains: "COFFEE".to_string(), ..rule("Contains") },
+            TransactionClassificationRule { raw_contains: "BLUE COFFEE".to_string(), ..rule("Longer contains") },
+            TransactionClassificationRule { raw_suffix: "SEATTLE".to_string(), ..rule("Suffix") },
+            TransactionClassificationRule { raw_prefix: "SQ *".to_string(), ..rule("Prefix") },
+            TransactionClassificationRule {
+                raw_prefix: "sq *blue".to_string(),
+                case_insensitive: true,
+                ..rule("Longer case-insensitive prefix")
+            },
+        ];
+
+        assert_eq!(classify(rules(), "SQ *BLUE COFFEE SEATTLE").description, "Longer case-insensitive prefix");
+        assert_eq!(classify(rules(), "SQ *RED COFFEE SEATTLE").description, "Prefix");
+        assert_eq!(classify(rules(), "BLUE COFFEE SEATTLE").description, "Suffix");
+        assert_eq!(classify(rules(), "BLUE COFFEE").description, "Longer contains");
+        assert_eq!(classify(rules(), "RED COFFEE").description, "Contains");
+        assert_eq!(classify(rules(), "COFFEE").description, "Contains");
+    }
+
+    #[test]
+    fn rejects_rules_without_exactly_one_pattern() {
+        assert!(matches!(
+            TransactionClassifier::new(vec![rule("None")]),
+            Err(RuleError::NoPattern { index: 0 })
+        ));
+        assert!(matches!(
+            TransactionClassifier::new(vec![TransactionClassificationRule {
+                raw_prefix: "A".to_string(),
+                raw_suffix: "B".to_string(),
+                ..rule("Both")
+            }]),
+            Err(RuleError::MultiplePatterns { index: 0 })
+        ));
+        assert!(matches!(
+            TransactionClassifier::new(vec![TransactionClassificationRule {
+                raw_regex: "(".to_string(),
+                ..rule("Bad regex")
+            }]),
+            Err(RuleError::Regex { index: 0, .. })
+        ));
+        assert!(matches!(
+            TransactionClassifier::new(vec![TransactionClassificationRule {
+                raw_prefix: "COSTCO".to_string(),
+                splits: vec![Split { category: "Household".to_string(), ..Default::default() }],
+                ..rule("Bad split")
+            }]),
+            Err(RuleError::Split { index: 0, source: SplitError::NoShare })
+        ));
+    }
+
+    fn classify_in_context(
+        rules: Vec<TransactionClassificationRule>,
+        source: &str,
+        raw_category: &str,
+        date: NaiveDate,
+        cents: i64,
+    ) -> Transaction {
+        TransactionClassifier::new(rules)
+            .unwrap()
+            .classify_transaction(source, Transaction {
+                raw_category: raw_category.to_string(),
+                ..test_transaction(date, "AMAZON MKTPLACE", cents)
+            })
+            .0
+    }
+
+    #[test]
+    fn applies_source_sign_amount_date_and_raw_category_predicates() {
+        let date = NaiveDate::from_ymd(2023, 6, 1);
+        let rules = || vec![
+            TransactionClassificationRule { raw_prefix: "AMAZON".to_string(), ..rule("Amazon") },
+            TransactionClassificationRule {
+                raw_prefix: "AMAZON".to_string(),
+                source: "PayPal".to_string(),
+                ..rule("Amazon via PayPal")
+            },
+            TransactionClassificationRule {
+                raw_prefix: "AMAZON".to_string(),
+                sign: Some(Sign::Positive),
+                ..rule("Amazon refund")
+            },
+            TransactionClassificationRule {
+                raw_prefix: "AMAZON".to_string(),
+                max_amount: Some(Money::from_cents(-50000)),
+                ..rule("Amazon big purchase")
+            },
+            TransactionClassificationRule {
+                raw_prefix: "AMAZON".to_string(),
+                start_date: Some(NaiveDate::from_ymd(2024, 1, 1)),
+                ..rule("Amazon 2024")
+            },
+            TransactionClassificationRule {
+                raw_prefix: "AMAZON".to_string(),
+                raw_category: "Groceries".to_string(),
+                ..rule("Amazon Fresh")
+            },
+        ];
+
+        assert_eq!(classify_in_context(rules(), "Card", "", date, -1000).description, "Amazon");
+        assert_eq!(classify_in_context(rules(), "PayPal", "", date, -1000).description, "Amazon via PayPal");
+        assert_eq!(classify_in_context(rules(), "Card", "", date, 1000).description, "Amazon refund");
+        assert_eq!(classify_in_context(rules(), "Card", "", date, -50000).description, "Amazon big purchase");
+        assert_eq!(
+            classify_in_context(rules(), "Card", "", NaiveDate::from_ymd(2024, 2, 1), -1000).description,
+            "Amazon 2024"
+        );
+        assert_eq!(classify_in_context(rules(), "Card", "Groceries", date, -1000).description, "Amazon Fresh");
+    }
+
+    #[test]
+    fn prefers_rule_with_most_predicates_over_longer_pattern() {
+        let date = NaiveDate::from_ymd(2023, 6, 1);
+        let rules = || vec![
+            TransactionClassificationRule { raw_prefix: "AMAZON MKTPLACE".to_string(), ..rule("Long prefix") },
+            TransactionClassificationRule {
+                raw_contains: "AMAZON".to_string(),
+                source: "Card".to_string(),
+                sign: Some(Sign::Negative),
+                ..rule("Card purchase")
+            },
+            TransactionClassificationRule {
+                raw_prefix: "AMAZON".to_string(),
+                source: "Card".to_string(),
+                ..rule("Card")
+            },
+        ];
+
+        assert_eq!(classify_in_context(rules(), "Card", "", date, -1000).description, "Card purchase");
+        assert_eq!(classify_in_context(rules(), "Card", "", date, 1000).description, "Card");
+        assert_eq!(classify_in_context(rules(), "PayPal", "", date, -1000).description, "Long prefix");
+    }
+
+    #[test]
+    fn prefers_later_rule_among_equally_specific_rules() {
+        let rules = vec![
+            TransactionClassificationRule { raw_prefix: "ACME".to_string(), ..rule("First") },
+            TransactionClassificationRule { raw_prefix: "ACME".to_string(), ..rule("Second") },
+        ];
+
+        assert_eq!(classify(rules, "ACME FALAFEL").description, "Second");
+    }
 }
@@ -1,61 +1,91 @@
 #![allow(dead_code)]
 
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::io::{self, Write};
 
 use chrono::NaiveDate;
 
-use crate::file_io::read_input;
-use crate::transactions::csv_record_to_transaction;
+use crate::error::Result;
+use crate::file_io::{
+    read_classified_transactions, read_transactions, read_transactions_and_duplicates, InputOptions,
+};
+use crate::money::Money;
 
-pub fn print_all_transactions() {
-    for (source, csv_config, csv_records) in read_input(PathBuf::from("input")) {
-        println!("*** {} ***", source);
-        for csv_record in csv_records {
-            println!("{:?}", csv_record_to_transaction(&csv_record, &csv_config));
+pub fn print_all_transactions(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
+    for (source, transactions) in read_transactions(input)? {
+        writeln!(out, "*** {} ***", source)?;
+        for transaction in transactions {
+            writeln!(out, "{:?}", transaction)?;
         }
-        println!();
+        writeln!(out)?;
     }
+    Ok(())
 }
 
-pub fn print_all_transactions_as_csv() {
-    println!("Date,Description,Category,Expense,Income,Memo,Source");
-    for (source, csv_config, csv_records) in read_input(PathBuf::from("input")) {
-        for csv_record in csv_records {
-            let transaction = csv_record_to_transaction(&csv_record, &csv_config);
-            // if is_transfer(&*transaction.raw_description) { continue; }
-
-            let expense = if transaction.amount <= 0.0 { -transaction.amount } else { 0.0 };
-            let income = if transaction.amount > 0.0 { transaction.amount } else { 0.0 };
-            println!("\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"\",\"{}\"",
-                     transaction.date.format("%m/%d/%Y"),
-                     transaction.raw_description, transaction.raw_category, expense, income, source);
+pub fn print_all_transactions_as_csv(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer
+        .write_record([
+            "Date",
+            "Description",
+            "Category",
+            "Expense",
+            "Income",
+            "Memo",
+            "Source",
+            "Classified Description",
+            "Classified Category",
+        ])
+        .map_err(io::Error::from)?;
+    for (source, transactions) in read_classified_transactions(input)? {
+        for transaction in transactions {
+            let expense = if transaction.amount.is_positive() { Money::ZERO } else { -transaction.amount };
+            let income = if transaction.amount.is_positive() { transaction.amount } else { Money::ZERO };
+            writer
+                .write_record([
+                    transaction.date.format("%m/%d/%Y").to_string(),
+                    transaction.raw_description,
+                    transaction.raw_category,
+                    expense.to_string(),
+                    income.to_string(),
+                    transaction.notes,
+                    source.clone(),
+                    transaction.description,
+                    transaction.category,
+                ])
+                .map_err(io::Error::from)?;
         }
     }
+    writer.flush()?;
+    Ok(())
 }
 
-fn _is_transfer(description: &str) -> bool
-{
-    let transfer_descriptions = HashSet::from(
-        ["BA ELECTRONIC PAYMENT", "BANK OF AMERICA", "CAPITAL ONE", "CITI AUTOPAY",
-            "CITI CREDIT CARD", "CREDIT BALANCE REFUND DEBIT", "CREDIT CARD PAYMENT",
-            "DEPOSIT FROM SHARE 01", "DIGITAL DEPOSIT FROM SHARE 01",
-            "DIGITAL DEPOSIT FROM SHARE 09", "DIGITAL WITHDRAWAL TO SHARE 01",
-            "DIGITAL WITHDRAWAL TO SHARE 09", "PADDLE", "PAYMENT THANK YOU", "PAYPAL",
-            "PAYPAL TRANSFER", "WITHDRAWAL TO SHARE 09"]);
-    transfer_descriptions.contains(description)
-}
-
-pub fn summarize_transactions() {
-    for (source, csv_config, cvs_records) in read_input(PathBuf::from("input")) {
-        println!("{}", source);
+pub fn summarize_transactions(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
+    for (source, transactions) in read_transactions(input)? {
+        writeln!(out, "{}", source)?;
         let mut min_date = NaiveDate::from_ymd(3000, 1, 1);
         let mut max_date = NaiveDate::from_ymd(1000, 1, 1);
-        for csv_record in cvs_records {
-            let transaction = csv_record_to_transaction(&csv_record, &csv_config);
+        for transaction in transactions {
             min_date = min_date.min(transaction.date);
             max_date = max_date.max(transaction.date);
         }
-        println!("   Date range: {} to {}", min_date, max_date);
+        writeln!(out, "   Date range: {} to {}", min_date, max_date)?;
+    }
+    Ok(())
+}
+
+pub fn print_duplicates(input: &InputOptions, out: &mut dyn Write) -> Result<()> {
+    let (_sources, duplicates) = read_transactions_and_duplicates(input)?;
+    writeln!(out, "Removed {} duplicate transactions", duplicates.len())?;
+    for duplicate in &duplicates {
+        writeln!(
+            out,
+            "  {} / {}: {}, \"{}\", {}",
+            duplicate.source,
+            duplicate.path.display(),
+            duplicate.transaction.date,
+            duplicate.transaction.raw_description,
+            duplicate.transaction.amount
+        )?;
     }
+    Ok(())
 }
@@ -1,15 +1,31 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::str;
 
 use chrono::NaiveDate;
 use csv::StringRecord;
+use regex::Regex;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+use crate::encoding::{decode, Encoding, EncodingError};
+use crate::money::Money;
+
+/// How the files of one source are read, from its config.json. Apart from the encoding and, for
+/// QIF, the date format, settings apply only to the CSV format. Each column is given by its index
+/// or by its name in the header row (`date_column` and so on), which may follow lines to skip and
+/// precede a footer to trim. The dialect settings cover files that are not comma-separated UTF-8
+/// with "1,234.56" amounts.
+#[derive(Clone, Debug, Deserialize)]
 pub struct CsvConfig {
     #[serde(default)]
     pub source_alias: String,
+    #[serde(default)]
+    pub format: InputFormat,
+    #[serde(default = "usize::max_value")]
     date_index: usize,
+    #[serde(default)]
     date_format: String,
+    #[serde(default = "usize::max_value")]
     description_index: usize,
     #[serde(default = "usize::max_value")]
     category_index: usize,
@@ -19,87 +35,470 @@ pub struct CsvConfig {
     debit_index: usize,
     #[serde(default = "usize::max_value")]
     credit_index: usize,
+    #[serde(default)]
+    date_column: String,
+    #[serde(default)]
+    description_column: String,
+    #[serde(default)]
+    category_column: String,
+    #[serde(default)]
+    amount_column: String,
+    #[serde(default)]
+    debit_column: String,
+    #[serde(default)]
+    credit_column: String,
+    /// A column telling debits from credits, such as "DR" and "CR", whose values are mapped in
+    /// `type_values`. The amount's sign then comes from the type alone, so `invert_amounts`
+    /// cannot be set as well.
+    #[serde(default = "usize::max_value")]
+    type_index: usize,
+    #[serde(default)]
+    type_column: String,
+    #[serde(default)]
+    type_values: HashMap<String, TransactionType>,
+    #[serde(default)]
+    debit_sign: DebitSign,
+    /// Negates every amount, for sources that show charges as positive.
+    #[serde(default)]
+    invert_amounts: bool,
+    /// Lines before the header row.
+    #[serde(default)]
+    skip_lines: usize,
+    /// Instead of `skip_lines`, the header row is the first line matching this.
+    #[serde(default)]
+    header_regex: String,
+    /// Lines at the end that are not transactions, not counting blank lines.
+    #[serde(default)]
+    footer_lines: usize,
+    /// The footer starts at the first line after the header matching this.
+    #[serde(default)]
+    footer_regex: String,
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+    #[serde(default = "default_quote")]
+    quote: char,
+    #[serde(default)]
+    encoding: Encoding,
+    /// Defaults to '.' with a decimal comma, and to ',' otherwise.
+    #[serde(default)]
+    thousands_separator: Option<char>,
+    #[serde(default = "default_decimal_separator")]
+    decimal_separator: char,
 }
 
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_quote() -> char {
+    '"'
+}
+
+fn default_decimal_separator() -> char {
+    '.'
+}
+
+impl CsvConfig {
+    pub fn date_format(&self) -> &str {
+        &self.date_format
+    }
+
+    fn thousands_separator(&self) -> char {
+        self.thousands_separator.unwrap_or(if self.decimal_separator == ',' { '.' } else { ',' })
+    }
+
+    /// Decodes the contents of a file in any format from the configured encoding.
+    pub fn decode(&self, bytes: &[u8]) -> Result<String, LayoutError> {
+        decode(bytes, self.encoding).map_err(LayoutError::Encoding)
+    }
+
+    /// A CSV reader for the configured delimiter and quote character.
+    pub fn reader<'a>(&self, contents: &'a str) -> Result<csv::Reader<&'a [u8]>, LayoutError> {
+        let byte = |option, value: char| {
+            Some(value).filter(char::is_ascii).map(|value| value as u8).ok_or(LayoutError::NotAscii { option })
+        };
+        Ok(csv::ReaderBuilder::new()
+            .delimiter(byte("delimiter", self.delimiter)?)
+            .quote(byte("quote", self.quote)?)
+            .from_reader(contents.as_bytes()))
+    }
+
+    /// Cuts the lines before the header row and the footer from the contents of a CSV file,
+    /// returning what remains, starting with the header row, and the number of lines cut before.
+    pub fn trim_lines<'a>(&self, contents: &'a str) -> Result<(&'a str, u64), LayoutError> {
+        let lines: Vec<(usize, &str)> = contents
+            .split_inclusive('\n')
+            .scan(0, |offset, line| {
+                let start = *offset;
+                *offset += line.len();
+                Some((start, line.trim_end_matches(['\r', '\n'])))
+            })
+            .collect();
+
+        let header = if self.header_regex.is_empty() {
+            self.skip_lines
+        } else {
+            let regex = compile("header_regex", &self.header_regex)?;
+            lines
+                .iter()
+                .position(|(_start, line)| regex.is_match(line))
+                .ok_or_else(|| LayoutError::NoHeader { regex: self.header_regex.clone() })?
+        };
+        let header = header.min(lines.len());
+
+        let mut end = lines.len();
+        if !self.footer_regex.is_empty() {
+            let regex = compile("footer_regex", &self.footer_regex)?;
+            if let Some(footer) = lines.iter().skip(header + 1).position(|(_start, line)| regex.is_match(line)) {
+                end = header + 1 + footer;
+            }
+        }
+        let mut footer_lines = self.footer_lines;
+        while footer_lines > 0 && end > header + 1 {
+            end -= 1;
+            if !lines[end].1.trim().is_empty() {
+                footer_lines -= 1;
+            }
+        }
+
+        let start_offset = lines.get(header).map_or(contents.len(), |(start, _line)| *start);
+        let end_offset = lines.get(end).map_or(contents.len(), |(start, _line)| *start);
+        Ok((&contents[start_offset..end_offset.max(start_offset)], header as u64))
+    }
+
+    /// Checks that the columns every CSV file needs are given, by index or by name, and that the
+    /// separators of amounts can be told apart.
+    pub fn check(&self) -> Result<(), LayoutError> {
+        if self.thousands_separator() == self.decimal_separator {
+            return Err(LayoutError::SameSeparators { separator: self.decimal_separator });
+        }
+        let given = |index: usize, name: &str| index != usize::MAX || !name.is_empty();
+        if !given(self.date_index, &self.date_column) || self.date_format.is_empty() {
+            return Err(LayoutError::MissingColumn { name: "date_index or date_column, and date_format" });
+        }
+        if !given(self.description_index, &self.description_column) {
+            return Err(LayoutError::MissingColumn { name: "description_index or description_column" });
+        }
+        let has_amount = given(self.amount_index, &self.amount_column)
+            || given(self.debit_index, &self.debit_column) && given(self.credit_index, &self.credit_column);
+        if !has_amount {
+            return Err(LayoutError::MissingColumn { name: "an amount column, or both debit and credit columns" });
+        }
+        if self.invert_amounts && (self.type_index != usize::MAX || !self.type_column.is_empty()) {
+            return Err(LayoutError::Conflict { first: "invert_amounts", second: "a type column" });
+        }
+        Ok(())
+    }
+
+    /// Returns a copy with the indexes of columns given by name looked up in the header row.
+    pub fn with_header(&self, header: &StringRecord) -> Result<CsvConfig, LayoutError> {
+        let mut csv_config = self.clone();
+        let columns = [
+            (&self.date_column, &mut csv_config.date_index),
+            (&self.description_column, &mut csv_config.description_index),
+            (&self.category_column, &mut csv_config.category_index),
+            (&self.amount_column, &mut csv_config.amount_index),
+            (&self.debit_column, &mut csv_config.debit_index),
+            (&self.credit_column, &mut csv_config.credit_index),
+            (&self.type_column, &mut csv_config.type_index),
+        ];
+        for (name, index) in columns {
+            if !name.is_empty() {
+                *index = header
+                    .iter()
+                    .position(|field| field.trim().eq_ignore_ascii_case(name.trim()))
+                    .ok_or_else(|| LayoutError::UnknownColumn { name: name.clone() })?;
+            }
+        }
+        Ok(csv_config)
+    }
+}
+
+fn compile(option: &'static str, pattern: &str) -> Result<Regex, LayoutError> {
+    Regex::new(pattern).map_err(|source| LayoutError::Regex { option, source })
+}
+
+/// Problems finding the header row and columns of a CSV file.
 #[derive(Debug)]
+pub enum LayoutError {
+    Encoding(EncodingError),
+    NotAscii { option: &'static str },
+    SameSeparators { separator: char },
+    Conflict { first: &'static str, second: &'static str },
+    Regex { option: &'static str, source: regex::Error },
+    NoHeader { regex: String },
+    MissingColumn { name: &'static str },
+    UnknownColumn { name: String },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Encoding(source) => write!(f, "{}", source),
+            LayoutError::NotAscii { option } => write!(f, "{} must be an ASCII character", option),
+            LayoutError::SameSeparators { separator } => {
+                write!(f, "thousands_separator and decimal_separator are both '{}'", separator)
+            }
+            LayoutError::Conflict { first, second } => write!(f, "{} cannot be used with {}", first, second),
+            LayoutError::Regex { option, source } => write!(f, "invalid {}: {}", option, source),
+            LayoutError::NoHeader { regex } => write!(f, "no line matches header_regex \"{}\"", regex),
+            LayoutError::MissingColumn { name } => write!(f, "config needs {}", name),
+            LayoutError::UnknownColumn { name } => write!(f, "no column named \"{}\" in header", name),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LayoutError::Encoding(source) => Some(source),
+            LayoutError::Regex { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionType {
+    Debit,
+    Credit,
+}
+
+/// How the debit column writes money going out.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DebitSign {
+    /// As positive numbers. A negative debit is ignored in favor of the credit column, and is an
+    /// error if that is empty.
+    #[default]
+    Positive,
+    /// As negative numbers, the other way around.
+    Negative,
+    /// With either sign, as some banks mix both.
+    Any,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    /// OFX or QFX downloads, in either the SGML (1.x) or XML (2.x) flavor.
+    Ofx,
+    /// Quicken Interchange Format exports of bank, credit card or cash accounts.
+    Qif,
+}
+
+impl InputFormat {
+    /// Lowercase file extensions read for this format.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            InputFormat::Csv => &["csv"],
+            InputFormat::Ofx => &["ofx", "qfx"],
+            InputFormat::Qif => &["qif"],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Transaction {
     pub date: NaiveDate,
     pub raw_description: String,
     pub raw_category: String,
-    pub amount: f32,
+    pub amount: Money,
     pub description: String,
     pub category: String,
+    pub notes: String,
+    /// Set by a manual override to leave the transaction out of reports.
+    pub excluded: bool,
+    /// The bank's own ID for the transaction, such as an OFX FITID, or empty if it has none.
+    pub bank_id: String,
 }
 
-pub fn csv_record_to_transaction(csv_record: &StringRecord, csv_config: &CsvConfig) -> Transaction {
+/// A transaction as read from a file, not yet classified, for tests to adjust as needed.
+#[cfg(test)]
+pub fn test_transaction(date: NaiveDate, raw_description: &str, cents: i64) -> Transaction {
     Transaction {
-        date: NaiveDate::parse_from_str(
-            csv_record.get(csv_config.date_index).unwrap(),
-            &csv_config.date_format,
-        )
-            .unwrap(),
-        raw_description: get_string_field_value(csv_record, csv_config.description_index),
-        raw_category: get_string_field_value(csv_record, csv_config.category_index),
-        amount: get_amount(csv_record, csv_config),
+        date,
+        raw_description: raw_description.to_string(),
+        raw_category: "".to_string(),
+        amount: Money::from_cents(cents),
         description: "".to_string(),
         category: "".to_string(),
+        notes: "".to_string(),
+        excluded: false,
+        bank_id: "".to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    MissingField { index: usize },
+    FieldCount { expected: u64, found: u64 },
+    Date { value: String, format: String, source: chrono::ParseError },
+    Amount { value: String },
+    TransactionType { value: String },
+    DebitSign { value: String, debit_sign: DebitSign },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::MissingField { index } => write!(f, "no field at index {}", index),
+            RecordError::FieldCount { expected, found } => {
+                write!(f, "found {} fields, but the header and previous rows have {}", found, expected)
+            }
+            RecordError::Date { value, format, source } => {
+                write!(f, "cannot parse date \"{}\" with format \"{}\": {}", value, format, source)
+            }
+            RecordError::Amount { value } => write!(f, "cannot parse amount \"{}\"", value),
+            RecordError::TransactionType { value } => {
+                write!(f, "transaction type \"{}\" is not in type_values", value)
+            }
+            RecordError::DebitSign { value, debit_sign } => write!(
+                f,
+                "debit \"{}\" is not {} and there is no credit; check debit_sign",
+                value,
+                match debit_sign {
+                    DebitSign::Positive => "positive",
+                    DebitSign::Negative => "negative",
+                    DebitSign::Any => "nonzero",
+                }
+            ),
+        }
     }
 }
 
-fn get_string_field_value(csv_record: &StringRecord, field_index: usize) -> String {
-    if field_index == usize::MAX { return "".to_string(); }
+impl std::error::Error for RecordError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RecordError::Date { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+pub fn csv_record_to_transaction(
+    csv_record: &StringRecord,
+    csv_config: &CsvConfig,
+) -> Result<Transaction, RecordError> {
+    Ok(Transaction {
+        date: get_date(csv_record, csv_config)?,
+        raw_description: get_string_field_value(csv_record, csv_config.description_index)?,
+        raw_category: get_string_field_value(csv_record, csv_config.category_index)?,
+        amount: get_amount(csv_record, csv_config)?,
+        description: "".to_string(),
+        category: "".to_string(),
+        notes: "".to_string(),
+        excluded: false,
+        bank_id: "".to_string(),
+    })
+}
 
+fn get_field(csv_record: &StringRecord, field_index: usize) -> Result<&str, RecordError> {
     csv_record
         .get(field_index)
-        .unwrap()
+        .ok_or(RecordError::MissingField { index: field_index })
+}
+
+fn get_date(csv_record: &StringRecord, csv_config: &CsvConfig) -> Result<NaiveDate, RecordError> {
+    let value = get_field(csv_record, csv_config.date_index)?;
+    NaiveDate::parse_from_str(value, &csv_config.date_format).map_err(|source| RecordError::Date {
+        value: value.to_string(),
+        format: csv_config.date_format.clone(),
+        source,
+    })
+}
+
+fn get_string_field_value(csv_record: &StringRecord, field_index: usize) -> Result<String, RecordError> {
+    if field_index == usize::MAX { return Ok("".to_string()); }
+
+    Ok(get_field(csv_record, field_index)?
         .trim()
-        .to_string()
+        .to_string())
 }
 
-fn get_amount(csv_record: &StringRecord, csv_config: &CsvConfig) -> f32 {
-    if csv_config.amount_index != usize::MAX {
-        return get_f32_field_value(csv_record, csv_config.amount_index);
+fn get_amount(csv_record: &StringRecord, csv_config: &CsvConfig) -> Result<Money, RecordError> {
+    let mut amount = get_unsigned_amount(csv_record, csv_config)?;
+    if csv_config.type_index != usize::MAX {
+        let value = get_field(csv_record, csv_config.type_index)?.trim();
+        let transaction_type = csv_config
+            .type_values
+            .iter()
+            .find(|(type_value, _transaction_type)| type_value.trim().eq_ignore_ascii_case(value))
+            .map(|(_type_value, transaction_type)| *transaction_type)
+            .ok_or_else(|| RecordError::TransactionType { value: value.to_string() })?;
+        amount = match transaction_type {
+            TransactionType::Debit => -amount.abs(),
+            TransactionType::Credit => amount.abs(),
+        };
     }
+    Ok(if csv_config.invert_amounts { -amount } else { amount })
+}
 
-    assert_ne!(csv_config.debit_index, usize::MAX);
-    assert_ne!(csv_config.credit_index, usize::MAX);
+/// The amount before any transaction type or inversion is applied.
+fn get_unsigned_amount(csv_record: &StringRecord, csv_config: &CsvConfig) -> Result<Money, RecordError> {
+    if csv_config.amount_index != usize::MAX {
+        return get_money_field_value(csv_record, csv_config.amount_index, csv_config);
+    }
 
-    let debit = get_f32_field_value(csv_record, csv_config.debit_index);
-    if debit > 0.0 {
-        return -debit;
+    let debit = get_money_field_value(csv_record, csv_config.debit_index, csv_config)?;
+    let outflow = match csv_config.debit_sign {
+        DebitSign::Positive if debit.is_positive() => Some(-debit),
+        DebitSign::Negative if debit.is_negative() => Some(debit),
+        DebitSign::Any if debit != Money::ZERO => Some(-debit.abs()),
+        _ => None,
+    };
+    if let Some(outflow) = outflow {
+        return Ok(outflow);
+    }
+    if debit != Money::ZERO && get_field(csv_record, csv_config.credit_index)?.trim().is_empty() {
+        return Err(RecordError::DebitSign {
+            value: get_field(csv_record, csv_config.debit_index)?.trim().to_string(),
+            debit_sign: csv_config.debit_sign,
+        });
     }
 
-    get_f32_field_value(csv_record, csv_config.credit_index)
+    get_money_field_value(csv_record, csv_config.credit_index, csv_config)
 }
 
-fn get_f32_field_value(csv_record: &StringRecord, field_index: usize) -> f32 {
-    let value_str = csv_record
-        .get(field_index)
-        .unwrap()
-        .replace(',', "");
-    if value_str.is_empty() { return 0.0; }
+fn get_money_field_value(
+    csv_record: &StringRecord,
+    field_index: usize,
+    csv_config: &CsvConfig,
+) -> Result<Money, RecordError> {
+    let value = get_field(csv_record, field_index)?;
+    if value.trim().is_empty() { return Ok(Money::ZERO); }
 
-    value_str
-        .parse()
-        .unwrap()
+    parse_amount(value, csv_config.thousands_separator(), csv_config.decimal_separator)
+        .ok_or_else(|| RecordError::Amount { value: value.to_string() })
+}
+
+/// Parses an amount written with the given separators, such as "1.234,56" with '.' and ','.
+pub fn parse_amount(value: &str, thousands_separator: char, decimal_separator: char) -> Option<Money> {
+    let normalized: String = value
+        .trim()
+        .chars()
+        .filter(|c| *c != thousands_separator)
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    normalized.parse().ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn csv_config(json: &str) -> CsvConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
     #[test]
     fn creates_transaction_from_csv_record() {
-        let csv_config = CsvConfig {
-            source_alias: "".to_string(),
-            date_index: 1,
-            date_format: "%m/%d/%Y".to_string(),
-            description_index: 2,
-            category_index: 4,
-            amount_index: 5,
-            debit_index: usize::MAX,
-            credit_index: usize::MAX,
-        };
+        let csv_config = csv_config(
+            r#"{"date_index": 1, "date_format": "%m/%d/%Y", "description_index": 2, "category_index": 4, "amount_index": 5}"#,
+        );
         let csv_record = StringRecord::from(vec![
             "ignore1",
             "2/12/2020",
@@ -109,10 +508,199 @@ mod tests {
             "-12.93",
         ]);
 
-        let transaction = csv_record_to_transaction(&csv_record, &csv_config);
+        let transaction = csv_record_to_transaction(&csv_record, &csv_config).unwrap();
 
         assert_eq!(transaction.date, NaiveDate::from_ymd(2020, 2, 12));
         assert_eq!(transaction.raw_description, "ACME FALAFEL");
-        assert_eq!(transaction.amount, -12.93);
+        assert_eq!(transaction.amount, Money::from_cents(-1293));
+    }
+
+    #[test]
+    fn reports_unparsable_date_and_amount() {
+        let csv_config = csv_config(r#"{"date_index": 0, "date_format": "%m/%d/%Y", "description_index": 1, "amount_index": 2}"#);
+
+        let bad_date = StringRecord::from(vec!["2020-02-12", "ACME FALAFEL", "-12.93"]);
+        assert!(matches!(
+            csv_record_to_transaction(&bad_date, &csv_config),
+            Err(RecordError::Date { .. })
+        ));
+
+        let bad_amount = StringRecord::from(vec!["2/12/2020", "ACME FALAFEL", "12.934"]);
+        assert!(matches!(
+            csv_record_to_transaction(&bad_amount, &csv_config),
+            Err(RecordError::Amount { .. })
+        ));
+
+        let missing_amount = StringRecord::from(vec!["2/12/2020", "ACME FALAFEL"]);
+        assert!(matches!(
+            csv_record_to_transaction(&missing_amount, &csv_config),
+            Err(RecordError::MissingField { index: 2 })
+        ));
+    }
+
+    #[test]
+    fn checks_columns_separators_and_conflicts_once_for_the_config() {
+        assert!(csv_config(r#"{"date_column": "Date", "date_format": "%m/%d/%Y", "description_index": 1, "amount_index": 2}"#)
+            .check()
+            .is_ok());
+        assert!(matches!(
+            csv_config(r#"{"description_index": 1, "amount_index": 2}"#).check(),
+            Err(LayoutError::MissingColumn { name: "date_index or date_column, and date_format" })
+        ));
+        assert!(matches!(
+            csv_config(r#"{"date_index": 0, "date_format": "%m/%d/%Y", "description_index": 1, "debit_index": 2}"#)
+                .check(),
+            Err(LayoutError::MissingColumn { .. })
+        ));
+        assert!(matches!(
+            csv_config(r#"{"date_index": 0, "date_format": "%m/%d/%Y", "description_index": 1, "amount_index": 2,
+                "thousands_separator": ",", "decimal_separator": ","}"#)
+                .check(),
+            Err(LayoutError::SameSeparators { separator: ',' })
+        ));
+        assert!(matches!(
+            csv_config(
+                r#"{"date_index": 0, "date_format": "%Y-%m-%d", "description_index": 1, "amount_index": 2,
+                    "type_column": "Type", "invert_amounts": true}"#
+            )
+            .check(),
+            Err(LayoutError::Conflict { first: "invert_amounts", .. })
+        ));
+    }
+
+    #[test]
+    fn trims_preamble_and_footer_and_finds_named_columns() {
+        let contents = "Account: 1234\r\nExported 2023-02-01\r\n\r\nPosting Date,Amount,Payee\r\n\
+            2/12/2020,-12.93,ACME FALAFEL\r\n2/13/2020,-4.50,SQ *COFFEE\r\n\r\nTotal,-17.43,\r\nEnd of report\r\n";
+        let by_regex = csv_config(
+            r#"{"date_column": "posting date", "date_format": "%m/%d/%Y", "description_column": "Payee",
+                "amount_column": "Amount", "header_regex": "^Posting Date,", "footer_regex": "^Total,"}"#,
+        );
+
+        let (trimmed, skipped) = by_regex.trim_lines(contents).unwrap();
+        assert_eq!(skipped, 3);
+        assert!(trimmed.starts_with("Posting Date,"));
+        assert!(trimmed.ends_with("COFFEE\r\n\r\n"));
+
+        let mut reader = csv::Reader::from_reader(trimmed.as_bytes());
+        let resolved = by_regex.with_header(reader.headers().unwrap()).unwrap();
+        let transactions: Vec<Transaction> = reader
+            .records()
+            .map(|record| csv_record_to_transaction(&record.unwrap(), &resolved).unwrap())
+            .collect();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[1].raw_description, "SQ *COFFEE");
+        assert_eq!(transactions[1].amount, Money::from_cents(-450));
+
+        let by_count = csv_config(r#"{"skip_lines": 3, "footer_lines": 2}"#);
+        assert_eq!(by_count.trim_lines(contents).unwrap().0, trimmed);
+
+        let unknown = csv_config(r#"{"date_column": "Date"}"#);
+        assert!(matches!(
+            unknown.with_header(&StringRecord::from(vec!["Posting Date"])),
+            Err(LayoutError::UnknownColumn { .. })
+        ));
+    }
+
+    #[test]
+    fn reads_european_dialect() {
+        let csv_config = csv_config(
+            r#"{"date_index": 0, "date_format": "%d.%m.%Y", "description_index": 1, "amount_index": 2,
+                "delimiter": ";", "quote": "'", "encoding": "latin-1",
+                "thousands_separator": ".", "decimal_separator": ","}"#,
+        );
+        let contents = csv_config.decode(b"Datum;Empf\xE4nger;Betrag\n01.02.2023;'B\xE4ckerei; Nord';-1.234,56\n").unwrap();
+
+        let mut reader = csv_config.reader(&contents).unwrap();
+        assert_eq!(&reader.headers().unwrap()[1], "Empfänger");
+        let transaction = csv_record_to_transaction(&reader.records().next().unwrap().unwrap(), &csv_config).unwrap();
+
+        assert_eq!(transaction.date, NaiveDate::from_ymd(2023, 2, 1));
+        assert_eq!(transaction.raw_description, "Bäckerei; Nord");
+        assert_eq!(transaction.amount, Money::from_cents(-123456));
+    }
+
+    #[test]
+    fn defaults_thousands_separator_to_point_with_decimal_comma() {
+        let csv_config = csv_config(
+            r#"{"date_index": 0, "date_format": "%d.%m.%Y", "description_index": 1, "amount_index": 2,
+                "decimal_separator": ","}"#,
+        );
+        assert!(csv_config.check().is_ok());
+
+        assert_eq!(
+            amounts(&csv_config, &[&["01.02.2023", "ACME", "-12,50"], &["02.02.2023", "GEHALT", "2.500,00"]]),
+            vec![Money::from_cents(-1250), Money::from_cents(250000)]
+        );
+    }
+
+    fn amounts(csv_config: &CsvConfig, rows: &[&[&str]]) -> Vec<Money> {
+        rows.iter()
+            .map(|row| csv_record_to_transaction(&StringRecord::from(row.to_vec()), csv_config).unwrap().amount)
+            .collect()
+    }
+
+    #[test]
+    fn inverts_amounts_of_sources_showing_charges_as_positive() {
+        let csv_config = csv_config(
+            r#"{"date_index": 0, "date_format": "%Y-%m-%d", "description_index": 1, "amount_index": 2, "invert_amounts": true}"#,
+        );
+
+        assert_eq!(
+            amounts(&csv_config, &[&["2023-01-05", "ACME", "12.93"], &["2023-01-06", "REFUND", "-5.00"]]),
+            vec![Money::from_cents(-1293), Money::from_cents(500)]
+        );
+    }
+
+    #[test]
+    fn reads_debits_by_configured_sign() {
+        let positive_debit = ["2023-01-05", "ACME", "12.93", ""];
+        let negative_debit = ["2023-01-06", "SAFEWAY", "-80.25", ""];
+        let credit = ["2023-01-07", "PAYROLL", "", "1500.00"];
+        let with_sign = |sign: &str| {
+            csv_config(&format!(
+                r#"{{"date_index": 0, "date_format": "%Y-%m-%d", "description_index": 1,
+                    "debit_index": 2, "credit_index": 3, "debit_sign": "{}"}}"#,
+                sign
+            ))
+        };
+
+        assert_eq!(
+            amounts(&with_sign("positive"), &[&positive_debit, &credit]),
+            vec![Money::from_cents(-1293), Money::from_cents(150000)]
+        );
+        assert_eq!(
+            amounts(&with_sign("negative"), &[&negative_debit, &credit]),
+            vec![Money::from_cents(-8025), Money::from_cents(150000)]
+        );
+        assert_eq!(
+            amounts(&with_sign("any"), &[&positive_debit, &negative_debit, &credit]),
+            vec![Money::from_cents(-1293), Money::from_cents(-8025), Money::from_cents(150000)]
+        );
+        assert!(matches!(
+            csv_record_to_transaction(&StringRecord::from(negative_debit.to_vec()), &with_sign("positive")),
+            Err(RecordError::DebitSign { .. })
+        ));
+        assert!(matches!(
+            csv_record_to_transaction(&StringRecord::from(positive_debit.to_vec()), &with_sign("negative")),
+            Err(RecordError::DebitSign { .. })
+        ));
+    }
+
+    #[test]
+    fn signs_amounts_by_transaction_type() {
+        let csv_config = csv_config(
+            r#"{"date_index": 0, "date_format": "%Y-%m-%d", "description_index": 1, "amount_index": 2,
+                "type_index": 3, "type_values": {"DR": "debit", "CR": "credit"}}"#,
+        );
+
+        assert_eq!(
+            amounts(&csv_config, &[&["2023-01-05", "ACME", "12.93", "DR"], &["2023-01-06", "PAYROLL", "-1500.00", " cr "]]),
+            vec![Money::from_cents(-1293), Money::from_cents(150000)]
+        );
+        assert!(matches!(
+            csv_record_to_transaction(&StringRecord::from(vec!["2023-01-07", "X", "1.00", "XX"]), &csv_config),
+            Err(RecordError::TransactionType { .. })
+        ));
     }
 }
@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::category_tree;
+use crate::file_io::SourceTransactions;
+use crate::money::Money;
+use crate::transactions::Transaction;
+
+pub const TRANSFER_CATEGORY: &str = "Transfer";
+const UNKNOWN_CATEGORY: &str = "Unknown";
+
+/// An outflow from one source paired with an equal inflow to another.
+#[derive(Debug)]
+pub struct TransferPair {
+    pub outflow_source: String,
+    pub outflow: Transaction,
+    pub inflow_source: String,
+    pub inflow: Transaction,
+}
+
+#[derive(Debug, Default)]
+pub struct TransferMatches {
+    pub pairs: Vec<TransferPair>,
+    /// Transactions classified as transfers, and unknown transactions with an equal and opposite
+    /// amount in another source, for which no counterpart was found.
+    pub unmatched: Vec<(String, Transaction)>,
+}
+
+type TransactionRef = (usize, usize);
+
+/// Pairs each outflow with an inflow of the same amount in a different source at most
+/// `day_window` days apart, preferring the closest date, and categorizes both as transfers.
+/// Both sides must be classified as transfers (or a subcategory) or unknown, so ordinary
+/// purchases and refunds that happen to match stay as they are.
+pub fn match_transfers(sources: &mut SourceTransactions, day_window: i64) -> TransferMatches {
+    let mut outflows: Vec<TransactionRef> = vec![];
+    let mut inflows_by_amount: HashMap<Money, Vec<TransactionRef>> = HashMap::new();
+    let mut sources_by_amount: HashMap<Money, HashSet<usize>> = HashMap::new();
+    for (source_index, (_source, transactions)) in sources.iter().enumerate() {
+        for (transaction_index, transaction) in transactions.iter().enumerate() {
+            if !is_eligible(transaction) {
+                continue;
+            }
+            let transaction_ref = (source_index, transaction_index);
+            sources_by_amount.entry(transaction.amount).or_default().insert(source_index);
+            if transaction.amount.is_negative() {
+                outflows.push(transaction_ref);
+            } else if transaction.amount.is_positive() {
+                inflows_by_amount.entry(transaction.amount).or_default().push(transaction_ref);
+            }
+        }
+    }
+    let get = |(source_index, transaction_index): TransactionRef| -> &Transaction {
+        &sources[source_index].1[transaction_index]
+    };
+    outflows.sort_by_key(|&outflow_ref| get(outflow_ref).date);
+
+    let mut matched_refs: Vec<(TransactionRef, TransactionRef)> = vec![];
+    for outflow_ref in outflows {
+        let outflow = get(outflow_ref);
+        let inflows = match inflows_by_amount.get_mut(&-outflow.amount) {
+            Some(inflows) => inflows,
+            None => continue,
+        };
+        let best = inflows
+            .iter()
+            .enumerate()
+            .filter(|(_, &inflow_ref)| {
+                let inflow = get(inflow_ref);
+                inflow_ref.0 != outflow_ref.0 && days_apart(outflow, inflow) <= day_window
+            })
+            .min_by_key(|(_, &inflow_ref)| (days_apart(outflow, get(inflow_ref)), get(inflow_ref).date))
+            .map(|(i, _)| i);
+        if let Some(i) = best {
+            matched_refs.push((outflow_ref, inflows.swap_remove(i)));
+        }
+    }
+
+    let mut matches = TransferMatches::default();
+    for &(outflow_ref, inflow_ref) in &matched_refs {
+        for &(source_index, transaction_index) in &[outflow_ref, inflow_ref] {
+            let transaction = &mut sources[source_index].1[transaction_index];
+            if !is_transfer(transaction) {
+                transaction.category = TRANSFER_CATEGORY.to_string();
+            }
+        }
+        matches.pairs.push(TransferPair {
+            outflow_source: sources[outflow_ref.0].0.clone(),
+            outflow: get_clone(sources, outflow_ref),
+            inflow_source: sources[inflow_ref.0].0.clone(),
+            inflow: get_clone(sources, inflow_ref),
+        });
+    }
+    let matched: HashSet<TransactionRef> = matched_refs
+        .iter()
+        .flat_map(|&(outflow_ref, inflow_ref)| vec![outflow_ref, inflow_ref])
+        .collect();
+    let has_counterpart = |source_index: usize, transaction: &Transaction| {
+        sources_by_amount
+            .get(&-transaction.amount)
+            .is_some_and(|source_indexes| source_indexes.iter().any(|&other| other != source_index))
+    };
+    for (source_index, (source, transactions)) in sources.iter().enumerate() {
+        for (transaction_index, transaction) in transactions.iter().enumerate() {
+            let is_candidate = is_transfer(transaction)
+                || (transaction.category == UNKNOWN_CATEGORY && has_counterpart(source_index, transaction));
+            if is_candidate && !matched.contains(&(source_index, transaction_index)) {
+                matches.unmatched.push((source.clone(), transaction.clone()));
+            }
+        }
+    }
+    matches
+}
+
+fn is_transfer(transaction: &Transaction) -> bool {
+    category_tree::is_within(&transaction.category, TRANSFER_CATEGORY)
+}
+
+fn is_eligible(transaction: &Transaction) -> bool {
+    is_transfer(transaction) || transaction.category == UNKNOWN_CATEGORY
+}
+
+fn days_apart(transaction1: &Transaction, transaction2: &Transaction) -> i64 {
+    (transaction1.date - transaction2.date).num_days().abs()
+}
+
+fn get_clone(sources: &SourceTransactions, (source_index, transaction_index): TransactionRef) -> Transaction {
+    sources[source_index].1[transaction_index].clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::transactions::test_transaction;
+
+    use super::*;
+
+    fn transaction(day: u32, raw_description: &str, cents: i64, category: &str) -> Transaction {
+        Transaction {
+            category: category.to_string(),
+            ..test_transaction(NaiveDate::from_ymd(2023, 2, day), raw_description, cents)
+        }
+    }
+
+    #[test]
+    fn pairs_outflow_with_closest_equal_inflow_in_another_source() {
+        let mut sources = vec![
+            ("Checking".to_string(), vec![transaction(1, "CITI AUTOPAY", -20000, "Transfer")]),
+            ("Card".to_string(), vec![
+                transaction(9, "PAYMENT THANK YOU", 20000, "Unknown"),
+                transaction(2, "PAYMENT THANK YOU", 20000, "Unknown"),
+            ]),
+        ];
+
+        let matches = match_transfers(&mut sources, 3);
+
+        assert_eq!(matches.pairs.len(), 1);
+        assert_eq!(matches.pairs[0].inflow.date, NaiveDate::from_ymd(2023, 2, 2));
+        assert_eq!(sources[1].1[0].category, "Unknown");
+        assert_eq!(sources[1].1[1].category, "Transfer");
+        assert_eq!(matches.unmatched.len(), 1);
+        assert_eq!(matches.unmatched[0].1.date, NaiveDate::from_ymd(2023, 2, 9));
+    }
+
+    #[test]
+    fn leaves_purchases_and_same_source_pairs_alone() {
+        let mut sources = vec![
+            ("Checking".to_string(), vec![
+                transaction(1, "SAFEWAY", -5000, "Groceries"),
+                transaction(1, "TRANSFER TO SAVINGS", -7500, "Transfer"),
+                transaction(1, "TRANSFER FROM SAVINGS", 7500, "Transfer"),
+            ]),
+            ("Card".to_string(), vec![transaction(2, "SAFEWAY REFUND", 5000, "Unknown")]),
+        ];
+
+        let matches = match_transfers(&mut sources, 3);
+
+        assert!(matches.pairs.is_empty());
+        assert_eq!(sources[1].1[0].category, "Unknown");
+        assert_eq!(matches.unmatched.len(), 2);
+    }
+
+    #[test]
+    fn ignores_inflows_outside_day_window() {
+        let mut sources = vec![
+            ("Checking".to_string(), vec![transaction(1, "PAYPAL TRANSFER", -3000, "Transfer")]),
+            ("PayPal".to_string(), vec![transaction(6, "BANK DEPOSIT", 3000, "Unknown")]),
+        ];
+
+        let matches = match_transfers(&mut sources, 3);
+
+        assert!(matches.pairs.is_empty());
+        assert_eq!(matches.unmatched.len(), 2);
+        assert_eq!(matches.unmatched[1].0, "PayPal");
+    }
+
+    #[test]
+    fn pairs_unknown_transactions_and_keeps_transfer_subcategories() {
+        let mut sources = vec![
+            ("Checking".to_string(), vec![
+                transaction(1, "ONLINE TRANSFER", -10000, "Unknown"),
+                transaction(3, "TO SAVINGS", -25000, "Transfer/Savings"),
+            ]),
+            ("Savings".to_string(), vec![
+                transaction(2, "ONLINE TRANSFER", 10000, "Unknown"),
+                transaction(3, "FROM CHECKING", 25000, "Unknown"),
+                transaction(3, "INTEREST", 125, "Unknown"),
+            ]),
+        ];
+
+        let matches = match_transfers(&mut sources, 3);
+
+        assert_eq!(matches.pairs.len(), 2);
+        assert_eq!(sources[0].1[0].category, "Transfer");
+        assert_eq!(sources[0].1[1].category, "Transfer/Savings");
+        assert_eq!(sources[1].1[0].category, "Transfer");
+        assert_eq!(sources[1].1[2].category, "Unknown");
+        assert!(matches.unmatched.is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::error::Result;
+use crate::exclusion_policy::ExclusionPolicy;
+use crate::file_io::{read_classified_transactions, InputOptions};
+use crate::money::Money;
+use crate::overrides::{transaction_ids, TransactionId};
+
+const UNKNOWN_CATEGORY: &str = "Unknown";
+
+/// The classification of every transaction at one point in time.
+#[derive(Debug, Default)]
+struct Snapshot {
+    categories_by_id: HashMap<TransactionId, String>,
+    category_totals: BTreeMap<String, Money>,
+}
+
+type Fingerprint = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+/// Polls the input directory, rules, overrides and exclusion policy every `interval` and,
+/// whenever a file changes, reclassifies everything and prints which transactions became
+/// recognized or unknown and which category totals moved. Problems reading the input go to
+/// stderr and the previous state is kept.
+pub fn watch(
+    input: &InputOptions,
+    load_policy: &dyn Fn() -> Result<ExclusionPolicy>,
+    exclusions_path: Option<&Path>,
+    interval: Duration,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut fingerprint = None;
+    let mut previous: Option<Snapshot> = None;
+    loop {
+        let current_fingerprint = input_fingerprint(input, exclusions_path);
+        if fingerprint.as_ref() != Some(&current_fingerprint) {
+            fingerprint = Some(current_fingerprint);
+            match take_snapshot(input, load_policy) {
+                Ok(snapshot) => {
+                    match &previous {
+                        Some(previous) => write_changes(previous, &snapshot, out)?,
+                        None => write_summary(&snapshot, out)?,
+                    }
+                    previous = Some(snapshot);
+                }
+                Err(error) => eprintln!("{}", error),
+            }
+            out.flush()?;
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn input_fingerprint(input: &InputOptions, exclusions_path: Option<&Path>) -> Fingerprint {
+    let mut fingerprint = vec![];
+    add_to_fingerprint(&input.input_dir, &mut fingerprint);
+    add_to_fingerprint(&input.rules_path, &mut fingerprint);
+    if let Some(overrides_path) = &input.overrides_path {
+        add_to_fingerprint(overrides_path, &mut fingerprint);
+    }
+    if let Some(ledger_path) = &input.ledger_path {
+        add_to_fingerprint(ledger_path, &mut fingerprint);
+    }
+    if let Some(exclusions_path) = exclusions_path {
+        add_to_fingerprint(exclusions_path, &mut fingerprint);
+    }
+    fingerprint.sort();
+    fingerprint.dedup();
+    fingerprint
+}
+
+fn add_to_fingerprint(path: &Path, fingerprint: &mut Fingerprint) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+    if metadata.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                add_to_fingerprint(&entry.path(), fingerprint);
+            }
+        }
+    } else {
+        fingerprint.push((path.to_path_buf(), metadata.modified().ok(), metadata.len()));
+    }
+}
+
+fn take_snapshot(
+    input: &InputOptions,
+    load_policy: &dyn Fn() -> Result<ExclusionPolicy>,
+) -> Result<Snapshot> {
+    let policy = load_policy()?;
+    let mut snapshot = Snapshot::default();
+    for (source, transactions) in read_classified_transactions(input)? {
+        for (transaction, id) in transactions.iter().zip(transaction_ids(&source, &transactions)) {
+            if !policy.excludes(transaction) {
+                *snapshot.category_totals.entry(transaction.category.clone()).or_default() += transaction.amount;
+            }
+            snapshot.categories_by_id.insert(id, transaction.category.clone());
+        }
+    }
+    Ok(snapshot)
+}
+
+fn write_summary(snapshot: &Snapshot, out: &mut dyn Write) -> Result<()> {
+    let unknown_count = snapshot
+        .categories_by_id
+        .values()
+        .filter(|category| *category == UNKNOWN_CATEGORY)
+        .count();
+    writeln!(
+        out,
+        "Watching {} transactions, {} unknown. Waiting for changes...",
+        snapshot.categories_by_id.len(),
+        unknown_count
+    )?;
+    Ok(())
+}
+
+fn write_changes(before: &Snapshot, after: &Snapshot, out: &mut dyn Write) -> Result<()> {
+    let mut recognized = vec![];
+    let mut unknown = vec![];
+    for (id, category) in &after.categories_by_id {
+        let previous_category = before.categories_by_id.get(id).map(String::as_str);
+        if category == UNKNOWN_CATEGORY && previous_category != Some(UNKNOWN_CATEGORY) {
+            unknown.push(id.to_string());
+        } else if category != UNKNOWN_CATEGORY && previous_category == Some(UNKNOWN_CATEGORY) {
+            recognized.push(format!("{} -> {}", id, category));
+        }
+    }
+    recognized.sort();
+    unknown.sort();
+
+    let mut moved = vec![];
+    let categories: BTreeSet<&String> =
+        before.category_totals.keys().chain(after.category_totals.keys()).collect();
+    for category in categories {
+        let old_total = before.category_totals.get(category).copied().unwrap_or_default();
+        let new_total = after.category_totals.get(category).copied().unwrap_or_default();
+        if old_total != new_total {
+            moved.push(format!("{}: {} -> {} ({})", category, old_total, new_total, new_total - old_total));
+        }
+    }
+
+    writeln!(out, "--- Input changed")?;
+    if recognized.is_empty() && unknown.is_empty() && moved.is_empty() {
+        writeln!(out, "No changes in classification")?;
+    }
+    write_section(out, "Newly recognized", &recognized)?;
+    write_section(out, "New unknowns", &unknown)?;
+    write_section(out, "Category totals", &moved)?;
+    Ok(())
+}
+
+fn write_section(out: &mut dyn Write, title: &str, lines: &[String]) -> Result<()> {
+    if !lines.is_empty() {
+        writeln!(out, "{}:", title)?;
+        for line in lines {
+            writeln!(out, "  {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn id(raw_description: &str, cents: i64) -> TransactionId {
+        TransactionId {
+            source: "Card".to_string(),
+            date: NaiveDate::from_ymd(2023, 8, 1),
+            amount: Money::from_cents(cents),
+            raw_description: raw_description.to_string(),
+            ordinal: 1,
+        }
+    }
+
+    fn snapshot(entries: &[(&str, i64, &str)]) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        for &(raw_description, cents, category) in entries {
+            snapshot.categories_by_id.insert(id(raw_description, cents), category.to_string());
+            *snapshot.category_totals.entry(category.to_string()).or_default() += Money::from_cents(cents);
+        }
+        snapshot
+    }
+
+    #[test]
+    fn reports_recognized_transactions_new_unknowns_and_moved_totals() {
+        let before = snapshot(&[("ACME FALAFEL", -1293, "Unknown"), ("NETFLIX", -1549, "Streaming")]);
+        let after = snapshot(&[
+            ("ACME FALAFEL", -1293, "Dining"),
+            ("NETFLIX", -1549, "Streaming"),
+            ("SQ *COFFEE", -450, "Unknown"),
+        ]);
+        let mut out = vec![];
+
+        write_changes(&before, &after, &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+--- Input changed
+Newly recognized:
+  Card 2023-08-01 -12.93 \"ACME FALAFEL\" #1 -> Dining
+New unknowns:
+  Card 2023-08-01 -4.50 \"SQ *COFFEE\" #1
+Category totals:
+  Dining: 0.00 -> -12.93 (-12.93)
+  Unknown: -12.93 -> -4.50 (8.43)
+");
+    }
+}